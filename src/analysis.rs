@@ -0,0 +1,370 @@
+use crate::bots::tablebase::Tablebase;
+use crate::bots::view::PlayerView;
+use crate::game::{Game, Move, Outcome, Update};
+use crate::hints::{self, Hint};
+use crate::log::{Event, GameLog};
+use crate::rules::{Goal, Rules};
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+/// Expected points a move has to give away to be reported as a mistake.
+pub const MISTAKE: f64 = 1.0;
+
+/// A move made with a choice of moves, rated against the best one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    /// Hand of the match, counting from 0.
+    pub hand: usize,
+    /// Move of the hand, counting from 1.
+    pub turn: usize,
+    pub seat: usize,
+    pub played: Hint,
+    pub best: Hint,
+}
+
+impl Decision {
+    /// Expected points given away by not making the best move.
+    pub fn lost(&self) -> f64 {
+        self.best.value - self.played.value
+    }
+}
+
+/// Every decision made in a recorded match.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    pub n_players: usize,
+    pub decisions: Vec<Decision>,
+}
+
+/// Replays a recorded match played under `rules` and rates every move that
+/// had an alternative, with only what the mover could see at the time, as
+/// the hints do. A move that could not have been made is an error.
+pub fn analyze(rules: &Rules, log: &GameLog, seed: u64) -> Result<Analysis> {
+    analyze_with(rules, log, seed, None)
+}
+
+/// Like `analyze`, but rates the endgames the tablebase holds exactly. Those
+/// are two-player block hands, where every tile is dealt and the mover knows
+/// the other hand.
+pub fn analyze_with(
+    rules: &Rules,
+    log: &GameLog,
+    seed: u64,
+    tablebase: Option<&Tablebase>,
+) -> Result<Analysis> {
+    let mut analysis = Analysis {
+        n_players: 0,
+        decisions: Vec::new(),
+    };
+
+    replay(rules, log, |replay, step| {
+        analysis.n_players = analysis.n_players.max(replay.game.n_players() as usize);
+
+        if let Step::Move(move_, index) = step {
+            let seat = move_.player();
+            let legal = replay.game.legal_moves(seat);
+
+            if legal.len() > 1 && legal.contains(move_) {
+                let seed = seed.wrapping_add(index as u64);
+                analysis
+                    .decisions
+                    .extend(replay.rate(move_, seed, tablebase));
+            }
+        }
+    })?;
+
+    Ok(analysis)
+}
+
+/// What happens next in a hand being replayed.
+pub(crate) enum Step<'a> {
+    /// A move about to be made, with the index of its event in the log.
+    Move(&'a Move, usize),
+    /// The hand is over.
+    Over(Outcome),
+}
+
+/// Walks through a recorded match, showing `visit` every hand before each of
+/// its moves and once it is over.
+pub(crate) fn replay(
+    rules: &Rules,
+    log: &GameLog,
+    mut visit: impl FnMut(&Replay, Step),
+) -> Result<()> {
+    let mut replay: Option<Replay> = None;
+
+    for (index, event) in log.events.iter().enumerate() {
+        match event {
+            Event::Deal {
+                hand,
+                players,
+                boneyard,
+                next,
+            } => {
+                let n_players = players.iter().filter(|tiles| !tiles.is_empty()).count();
+                let board = match rules.goal {
+                    Goal::Duplicate(_) => hand / n_players.max(1),
+                    _ => *hand,
+                };
+                let game = Game::from_deal(
+                    rules.clone(),
+                    n_players as i32,
+                    board,
+                    players.clone(),
+                    boneyard.clone(),
+                    *next,
+                );
+
+                replay = Some(Replay::new(*hand, board, game));
+            }
+            Event::Misdeal { .. } | Event::HandOver(_) => replay = None,
+            Event::Move(move_) => {
+                let hand = replay
+                    .as_mut()
+                    .ok_or_else(|| anyhow!("{} was made outside a hand", move_))?;
+
+                if move_.player() != hand.game.next as usize {
+                    return Err(anyhow!(
+                        "Hand {} move {}: {} out of turn",
+                        hand.hand,
+                        hand.turn + 1,
+                        move_
+                    ));
+                }
+
+                visit(hand, Step::Move(move_, index));
+                hand.play(move_)?;
+
+                if let Some(outcome) = hand.game.outcome() {
+                    visit(hand, Step::Over(outcome));
+                    replay = None;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// A hand being replayed, with what every seat can see of it.
+pub(crate) struct Replay {
+    pub hand: usize,
+    /// Moves made so far.
+    pub turn: usize,
+    pub game: Game,
+    pub views: Vec<PlayerView>,
+}
+
+impl Replay {
+    fn new(hand: usize, board: usize, game: Game) -> Replay {
+        let n_players = game.n_players();
+
+        let mut views = (0..n_players as usize)
+            .map(|seat| {
+                let tiles = game.players[seat]
+                    .iter()
+                    .filter(|piece| !game.opening_draws.contains(&(seat, **piece)))
+                    .copied()
+                    .collect();
+                PlayerView::new(game.rules.clone(), n_players, board, seat, tiles)
+            })
+            .collect::<Vec<_>>();
+
+        for view in views.iter_mut() {
+            view.game.next = game.next;
+        }
+        for (seat, piece) in game.opening_draws.clone() {
+            views[seat].drawn(piece);
+            for view in views.iter_mut() {
+                let _ = view.apply(seat, &Update::Draw);
+            }
+        }
+
+        Replay {
+            hand,
+            turn: 0,
+            game,
+            views,
+        }
+    }
+
+    fn play(&mut self, move_: &Move) -> Result<()> {
+        let seat = move_.player();
+        self.turn += 1;
+
+        let update = self
+            .game
+            .play(move_)
+            .map_err(|error| anyhow!("Hand {} move {}: {}", self.hand, self.turn, error))?;
+
+        if let (Update::Draw, Some(piece)) = (update, self.game.players[seat].last()) {
+            self.views[seat].drawn(*piece);
+        }
+        for view in self.views.iter_mut() {
+            let _ = view.apply(seat, &update);
+        }
+
+        Ok(())
+    }
+
+    /// Rates the move about to be made against the best one.
+    fn rate(&self, move_: &Move, seed: u64, tablebase: Option<&Tablebase>) -> Option<Decision> {
+        let seat = move_.player();
+        let hints = match tablebase.and_then(|tablebase| tablebase.moves(&self.game)) {
+            Some(moves) => {
+                let mut hints = moves
+                    .into_iter()
+                    .map(|(move_, value)| Hint {
+                        move_,
+                        value: value as f64,
+                    })
+                    .collect::<Vec<_>>();
+                hints.sort_by(|a, b| b.value.total_cmp(&a.value));
+                hints
+            }
+            None => hints::hints(&self.views[seat], seed),
+        };
+
+        Some(Decision {
+            hand: self.hand,
+            turn: self.turn + 1,
+            seat,
+            played: *hints.iter().find(|hint| hint.move_ == *move_)?,
+            best: *hints.first()?,
+        })
+    }
+}
+
+impl Analysis {
+    /// Decisions that gave away more than `MISTAKE` points, in order.
+    pub fn mistakes(&self) -> impl Iterator<Item = &Decision> {
+        self.decisions
+            .iter()
+            .filter(|decision| decision.lost() > MISTAKE)
+    }
+
+    /// Expected points the seat gave away over the match.
+    pub fn lost(&self, seat: usize) -> f64 {
+        self.decisions
+            .iter()
+            .filter(|decision| decision.seat == seat)
+            .map(|decision| decision.lost())
+            .sum()
+    }
+
+    pub fn to_json(&self) -> String {
+        let seats = (0..self.n_players)
+            .map(|seat| {
+                format!(
+                    "{{\"seat\":{},\"decisions\":{},\"mistakes\":{},\"lost\":{:.2}}}",
+                    seat,
+                    self.decisions.iter().filter(|d| d.seat == seat).count(),
+                    self.mistakes().filter(|d| d.seat == seat).count(),
+                    self.lost(seat)
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mistakes = self
+            .mistakes()
+            .map(|decision| {
+                format!(
+                    "{{\"hand\":{},\"turn\":{},\"seat\":{},\"played\":\"{}\",\"played_value\":{:.2},\"best\":\"{}\",\"best_value\":{:.2},\"lost\":{:.2}}}",
+                    decision.hand,
+                    decision.turn,
+                    decision.seat,
+                    decision.played.move_,
+                    decision.played.value,
+                    decision.best.move_,
+                    decision.best.value,
+                    decision.lost()
+                )
+            })
+            .collect::<Vec<_>>();
+
+        format!(
+            "{{\"seats\":[{}],\"mistakes\":[{}]}}",
+            seats.join(","),
+            mistakes.join(",")
+        )
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for seat in 0..self.n_players {
+            let mistakes = self
+                .mistakes()
+                .filter(|decision| decision.seat == seat)
+                .collect::<Vec<_>>();
+
+            writeln!(
+                f,
+                "Seat {}: {} mistakes, {:.1} points lost",
+                seat,
+                mistakes.len(),
+                self.lost(seat)
+            )?;
+
+            for decision in mistakes {
+                writeln!(
+                    f,
+                    "  hand {} move {}: played {} ({:+.1}), best {} ({:+.1}), lost {:.1}",
+                    decision.hand,
+                    decision.turn,
+                    decision.played.move_,
+                    decision.played.value,
+                    decision.best.move_,
+                    decision.best.value,
+                    decision.lost()
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::heuristics::RandomStrategy;
+    use crate::bots::player::Strategy;
+    use crate::bots::playout::play_match;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_analysis_of_a_recorded_hand() {
+        let rules = Rules::from_options("block hands=1").unwrap();
+        let mut strategies = (0..2)
+            .map(|seat| Box::new(RandomStrategy::new(seat)) as Box<dyn Strategy>)
+            .collect::<Vec<_>>();
+        let match_ = play_match(
+            rules.clone(),
+            &mut strategies,
+            &mut StdRng::seed_from_u64(3),
+        );
+
+        let log = GameLog::parse(&match_.log.to_string()).unwrap();
+        assert_eq!(match_.log.to_string(), log.to_string());
+        let analysis = analyze(&rules, &log, 1).unwrap();
+
+        assert_eq!(2, analysis.n_players);
+        assert!(!analysis.decisions.is_empty());
+        for decision in &analysis.decisions {
+            assert!(decision.lost() >= 0.0);
+        }
+        assert!(analysis.to_json().starts_with("{\"seats\":[{\"seat\":0,"));
+
+        let tablebase = Tablebase::generate(&rules, 3).unwrap();
+        let exact = analyze_with(&rules, &log, 1, Some(&tablebase)).unwrap();
+        assert_eq!(analysis.decisions.len(), exact.decisions.len());
+
+        let mut wrong = log.clone();
+        if let Some(Event::Move(move_)) = wrong.events.get_mut(5) {
+            *move_ = Move::Pass(7);
+        }
+        assert!(analyze(&rules, &wrong, 1).is_err());
+    }
+}
@@ -0,0 +1,404 @@
+use crate::bots::playout::play_match;
+use crate::bots::BotFiles;
+use crate::rules::Rules;
+use crate::scoring::Match;
+use anyhow::{anyhow, Result};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fmt;
+
+/// Rounds of the rating fit, plenty for a handful of entrants.
+const ELO_ROUNDS: usize = 200;
+
+/// A series of matches between bots, reproducible from its seed whatever
+/// the number of threads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arena {
+    pub rules: Rules,
+    /// Bot names, one per seat. The lineup moves round one seat every game.
+    pub lineup: Vec<String>,
+    pub games: usize,
+    pub seed: u64,
+    pub threads: usize,
+    /// Files the lineup's bots are read from, loaded once for the series.
+    files: BotFiles,
+}
+
+/// How one match ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+    /// Entrant playing as each player, sitting at the seat of the same
+    /// number when the match starts.
+    pub seats: [usize; 4],
+    pub scores: [i32; 4],
+    pub winner: Option<usize>,
+    /// Each player's points on every board.
+    pub boards: Vec<[i32; 4]>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub names: Vec<String>,
+    pub games: usize,
+    /// Matches won by each entrant, counting a partner's win.
+    pub wins: Vec<usize>,
+    /// Average final score less the best opposing score.
+    pub margins: Vec<f64>,
+    /// Average points on a board less the average of all players on it,
+    /// which takes the luck of the deal out in duplicate matches.
+    pub boards: Vec<f64>,
+    /// Ratings fitted to the head-to-head results, averaging zero.
+    pub elo: Vec<f64>,
+}
+
+impl Arena {
+    /// Reads `bots=`, `games=`, `seed=` and `threads=`; everything else is
+    /// taken as table options, e.g. `bots=pimc,random games=500 block
+    /// target=50`.
+    pub fn from_options(options: &str) -> Result<Arena> {
+        let mut arena = Arena {
+            rules: Rules::default(),
+            lineup: vec!["random".to_string(), "greedy".to_string()],
+            games: 1000,
+            seed: 0,
+            threads: std::thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1),
+            files: BotFiles::default(),
+        };
+        let mut table = Vec::new();
+
+        for word in options.split_whitespace() {
+            match word.split_once('=') {
+                Some(("bots", value)) => {
+                    arena.lineup = value.split(',').map(|name| name.to_string()).collect()
+                }
+                Some(("games", value)) => arena.games = value.parse()?,
+                Some(("seed", value)) => arena.seed = value.parse()?,
+                Some(("threads", value)) => arena.threads = value.parse()?,
+                _ => table.push(word),
+            }
+        }
+
+        if table
+            .first()
+            .map(|word| word.contains('='))
+            .unwrap_or(false)
+        {
+            table.insert(0, "block");
+        }
+        if !table.is_empty() {
+            arena.rules = Rules::from_options(&table.join(" "))?;
+        }
+
+        if !(2..=4).contains(&arena.lineup.len()) {
+            return Err(anyhow!("A lineup takes 2 to 4 bots".to_string()));
+        }
+        arena.files = BotFiles::load(&arena.lineup)?;
+        if let Some(name) = arena
+            .lineup
+            .iter()
+            .find(|name| arena.files.by_name(name, 0).is_none())
+        {
+            return Err(anyhow!("Unknown bot {}", name));
+        }
+
+        Ok(arena)
+    }
+
+    pub fn run(&self) -> Report {
+        let threads = self.threads.clamp(1, self.games.max(1));
+
+        let mut results = std::thread::scope(|scope| {
+            let workers = (0..threads)
+                .map(|thread| {
+                    scope.spawn(move || {
+                        (thread..self.games)
+                            .step_by(threads)
+                            .map(|game| (game, self.play(game)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        results.sort_by_key(|(game, _)| *game);
+
+        let results = results
+            .into_iter()
+            .map(|(_, result)| result)
+            .collect::<Vec<_>>();
+        self.report(&results)
+    }
+
+    /// Plays the given match of the series.
+    pub fn play(&self, game: usize) -> GameResult {
+        let seats = self.seats(game);
+        let match_ = self.record(game);
+
+        GameResult {
+            seats,
+            scores: match_.scores,
+            winner: match_.winner(),
+            boards: match_.boards(),
+        }
+    }
+
+    /// Plays the given match of the series, keeping every deal and move in
+    /// the match log.
+    pub fn record(&self, game: usize) -> Match {
+        let seed = self.seed.wrapping_mul(1_000_003).wrapping_add(game as u64);
+        let mut rng = StdRng::seed_from_u64(seed);
+        let seats = self.seats(game);
+
+        let mut strategies = (0..self.lineup.len())
+            .map(|seat| {
+                let name = &self.lineup[seats[seat]];
+                self.files
+                    .by_name(name, seed.wrapping_add(seat as u64))
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        play_match(self.rules.clone(), &mut strategies, &mut rng)
+    }
+
+    /// Entrant sitting at each seat when the given match starts.
+    fn seats(&self, game: usize) -> [usize; 4] {
+        let n_players = self.lineup.len();
+        let mut seats = [0; 4];
+        for (seat, entrant) in seats.iter_mut().enumerate().take(n_players) {
+            *entrant = (seat + game) % n_players;
+        }
+        seats
+    }
+
+    fn report(&self, results: &[GameResult]) -> Report {
+        let n_players = self.lineup.len();
+        let rules = &self.rules;
+        let mut wins = vec![0; n_players];
+        let mut margins = vec![0.0; n_players];
+        let mut boards = vec![0.0; n_players];
+        let n_boards = results
+            .iter()
+            .map(|result| result.boards.len())
+            .sum::<usize>()
+            .max(1) as f64;
+        // Head-to-head points and meetings, for the ratings.
+        let mut points = vec![vec![0.0; n_players]; n_players];
+        let mut meetings = vec![vec![0.0; n_players]; n_players];
+
+        for result in results {
+            for seat in 0..n_players {
+                let entrant = result.seats[seat];
+                let opponents = (0..n_players)
+                    .filter(|other| !rules.same_side(n_players, seat, *other))
+                    .collect::<Vec<_>>();
+
+                if let Some(winner) = result.winner {
+                    if rules.same_side(n_players, seat, winner) {
+                        wins[entrant] += 1;
+                    }
+                }
+
+                let scores = opponents.iter().map(|other| result.scores[*other]);
+                let margin = if rules.lowest_wins {
+                    scores.min().unwrap_or(0) - result.scores[seat]
+                } else {
+                    result.scores[seat] - scores.max().unwrap_or(0)
+                };
+                margins[entrant] += margin as f64 / results.len().max(1) as f64;
+
+                for board in &result.boards {
+                    let average = board.iter().sum::<i32>() as f64 / n_players as f64;
+                    boards[entrant] += (board[seat] as f64 - average) / n_boards;
+                }
+
+                for other in opponents {
+                    let rival = result.seats[other];
+                    let ahead = match rules.lowest_wins {
+                        true => result.scores[other].cmp(&result.scores[seat]),
+                        false => result.scores[seat].cmp(&result.scores[other]),
+                    };
+                    points[entrant][rival] += match ahead {
+                        std::cmp::Ordering::Greater => 1.0,
+                        std::cmp::Ordering::Equal => 0.5,
+                        std::cmp::Ordering::Less => 0.0,
+                    };
+                    meetings[entrant][rival] += 1.0;
+                }
+            }
+        }
+
+        let names = self
+            .lineup
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                match self.lineup[..i]
+                    .iter()
+                    .filter(|other| *other == name)
+                    .count()
+                {
+                    0 => name.clone(),
+                    copies => format!("{} ({})", name, copies + 1),
+                }
+            })
+            .collect();
+
+        Report {
+            names,
+            games: results.len(),
+            wins,
+            margins,
+            boards,
+            elo: elo(&points, &meetings),
+        }
+    }
+}
+
+/// Fits Bradley-Terry strengths to head-to-head results and turns them into
+/// Elo-style ratings. Every pair that met is given one drawn game more, so
+/// that an unbeaten entrant still gets a finite rating.
+fn elo(points: &[Vec<f64>], meetings: &[Vec<f64>]) -> Vec<f64> {
+    let n = points.len();
+    let mut strength = vec![1.0; n];
+
+    for _ in 0..ELO_ROUNDS {
+        for i in 0..n {
+            let mut won = 0.0;
+            let mut expected = 0.0;
+
+            for j in (0..n).filter(|j| meetings[i][*j] > 0.0) {
+                won += points[i][j] + 0.5;
+                expected += (meetings[i][j] + 1.0) / (strength[i] + strength[j]);
+            }
+            if expected > 0.0 {
+                strength[i] = won / expected;
+            }
+        }
+
+        let mean = strength.iter().map(|s: &f64| s.ln()).sum::<f64>() / n as f64;
+        for s in strength.iter_mut() {
+            *s /= mean.exp();
+        }
+    }
+
+    strength.iter().map(|s| 400.0 * s.log10()).collect()
+}
+
+impl Report {
+    /// Share of matches won, with the half-width of its 95% confidence
+    /// interval.
+    pub fn win_rate(&self, entrant: usize) -> (f64, f64) {
+        let games = self.games.max(1) as f64;
+        let rate = self.wins[entrant] as f64 / games;
+        (rate, 1.96 * (rate * (1.0 - rate) / games).sqrt())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{} games", self.games)?;
+        writeln!(
+            f,
+            "{:<12} {:>16} {:>8} {:>8} {:>8}",
+            "bot", "wins", "margin", "board", "elo"
+        )?;
+
+        for (entrant, name) in self.names.iter().enumerate() {
+            let (rate, error) = self.win_rate(entrant);
+            writeln!(
+                f,
+                "{:<12} {:>8.1}% ±{:>5.1} {:>8.1} {:>8.1} {:>8.0}",
+                name,
+                100.0 * rate,
+                100.0 * error,
+                self.margins[entrant],
+                self.boards[entrant],
+                self.elo[entrant]
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arena_is_reproducible() {
+        let mut arena =
+            Arena::from_options("bots=greedy,random games=40 seed=9 block hands=1").unwrap();
+        arena.threads = 3;
+        let report = arena.run();
+
+        arena.threads = 1;
+        assert_eq!(report, arena.run());
+
+        assert_eq!(40, report.games);
+        assert!(report.wins[0] > report.wins[1]);
+        assert!(report.elo[0] > 0.0 && report.elo[1] < 0.0);
+        assert!((report.elo[0] + report.elo[1]).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_duplicate_arena() {
+        let arena = Arena::from_options("bots=random,random games=10 block duplicate=3").unwrap();
+        let result = arena.play(4);
+
+        assert_eq!(3, result.boards.len());
+        for (player, score) in result.scores.iter().enumerate().take(2) {
+            let boards = result.boards.iter().map(|board| board[player]).sum::<i32>();
+            assert_eq!(*score, boards);
+        }
+
+        let report = arena.run();
+        assert!((report.boards[0] + report.boards[1]).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_arena_options() {
+        let arena = Arena::from_options("bots=ismcts,random,random,random target=20").unwrap();
+
+        assert_eq!(4, arena.lineup.len());
+        assert_eq!(crate::rules::Goal::Target(20), arena.rules.goal);
+        assert!(Arena::from_options("bots=oracle,random").is_err());
+        assert!(Arena::from_options("bots=random").is_err());
+    }
+
+    #[test]
+    fn test_bot_files_are_read_once() {
+        let (dir, id) = (std::env::temp_dir(), std::process::id());
+        let weights = dir.join(format!("rustominoes-arena-{}.weights", id));
+        let tablebase = dir.join(format!("rustominoes-arena-{}.tablebase", id));
+        crate::bots::evaluator::Weights::default()
+            .save(&weights)
+            .unwrap();
+        crate::bots::tablebase::Tablebase::generate(&Rules::block(), 2)
+            .unwrap()
+            .save(&tablebase)
+            .unwrap();
+
+        let options = format!(
+            "bots=evaluator:{},pimc:{} games=2 block hands=1",
+            weights.display(),
+            tablebase.display()
+        );
+        let arena = Arena::from_options(&options).unwrap();
+        let _ = std::fs::remove_file(&weights);
+        let _ = std::fs::remove_file(&tablebase);
+
+        // The series goes on without the files.
+        for name in &arena.lineup {
+            assert!(arena.files.by_name(name, 0).is_some());
+        }
+        assert!(Arena::from_options(&options).is_err());
+    }
+}
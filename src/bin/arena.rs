@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use rustominoes::analysis::analyze_with;
+use rustominoes::arena::Arena;
+use rustominoes::bots::evaluator::train;
+use rustominoes::bots::tablebase::Tablebase;
+use rustominoes::forecast::follow;
+use rustominoes::log::GameLog;
+
+/// Takes `key=value` words out of the options.
+fn take(options: &mut Vec<String>, key: &str) -> Option<String> {
+    let position = options
+        .iter()
+        .position(|word| word.starts_with(&format!("{}=", key)))?;
+    let word = options.remove(position);
+    Some(word[key.len() + 1..].to_string())
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+
+    match args.first().map(|arg| arg.as_str()) {
+        // Writes one match of the series in the game notation.
+        Some("record") => {
+            args.remove(0);
+            let game = take(&mut args, "game").unwrap_or_else(|| "0".to_string());
+            let arena = Arena::from_options(&args.join(" "))?;
+
+            print!("{}", arena.record(game.parse()?).log);
+        }
+        // Reports the mistakes made in a recorded match.
+        Some("analyze") => {
+            if args.len() < 2 {
+                return Err(anyhow!(
+                    "Usage: analyze FILE [format=json] [seed=S] [tablebase=FILE] [table options]"
+                        .to_string()
+                ));
+            }
+            let path = args.remove(1);
+            args.remove(0);
+            let format = take(&mut args, "format");
+            let tablebase = take(&mut args, "tablebase")
+                .map(Tablebase::load)
+                .transpose()?;
+            let arena = Arena::from_options(&args.join(" "))?;
+
+            let log = GameLog::parse(&std::fs::read_to_string(path)?)?;
+            let analysis = analyze_with(&arena.rules, &log, arena.seed, tablebase.as_ref())?;
+
+            match format.as_deref() {
+                Some("json") => println!("{}", analysis.to_json()),
+                _ => print!("{}", analysis),
+            }
+        }
+        // Shows the win bar after every move of a recorded match.
+        Some("odds") => {
+            if args.len() < 2 {
+                return Err(anyhow!(
+                    "Usage: odds FILE [samples=N] [seed=S] [table options]".to_string()
+                ));
+            }
+            let path = args.remove(1);
+            args.remove(0);
+            let samples = take(&mut args, "samples").unwrap_or_else(|| "200".to_string());
+            let arena = Arena::from_options(&args.join(" "))?;
+
+            let log = GameLog::parse(&std::fs::read_to_string(path)?)?;
+            for (move_, chances) in follow(&arena.rules, &log, samples.parse()?, arena.seed)? {
+                println!("{:<16} {}", move_.to_string(), chances);
+            }
+        }
+        // Tunes the evaluator's weights on the given records, or on matches
+        // of the series when there are none.
+        Some("train") => {
+            args.remove(0);
+            let out = take(&mut args, "out").unwrap_or_else(|| "weights.txt".to_string());
+            let epochs = take(&mut args, "epochs").unwrap_or_else(|| "1000".to_string());
+            let (files, mut options): (Vec<_>, Vec<_>) =
+                args.into_iter().partition(|arg| !arg.contains('='));
+            // Random players try every kind of move, which is what the
+            // weights are fitted on.
+            if !options.iter().any(|option| option.starts_with("bots=")) {
+                options.push("bots=random,random".to_string());
+            }
+            let arena = Arena::from_options(&options.join(" "))?;
+
+            let logs = match files.is_empty() {
+                true => (0..arena.games)
+                    .map(|game| arena.record(game).log)
+                    .collect(),
+                false => files
+                    .iter()
+                    .map(|path| GameLog::parse(&std::fs::read_to_string(path)?))
+                    .collect::<Result<Vec<_>>>()?,
+            };
+
+            let weights = train(&arena.rules, &logs, epochs.parse()?)?;
+            weights.save(&out)?;
+            print!("{}", weights);
+        }
+        // Builds an endgame tablebase for the table's rules.
+        Some("tablebase") => {
+            args.remove(0);
+            let out = take(&mut args, "out").unwrap_or_else(|| "endgames.tb".to_string());
+            let tiles = take(&mut args, "tiles").unwrap_or_else(|| "4".to_string());
+            let arena = Arena::from_options(&args.join(" "))?;
+
+            let tablebase = Tablebase::generate(&arena.rules, tiles.parse()?)?;
+            tablebase.save(&out)?;
+            println!("{} positions written to {}", tablebase.len(), out);
+        }
+        _ => {
+            let arena = Arena::from_options(&args.join(" "))?;
+
+            println!("{} games between {}", arena.games, arena.lineup.join(", "));
+            print!("{}", arena.run());
+        }
+    }
+
+    Ok(())
+}
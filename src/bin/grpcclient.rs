@@ -4,5 +4,8 @@ use anyhow::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    start_client().await
+    let (options, game_id): (Vec<_>, Vec<_>) =
+        std::env::args().skip(1).partition(|arg| arg.contains('='));
+    let game_id = game_id.first().cloned().unwrap_or_default();
+    start_client(&game_id, &options.join(" ")).await
 }
@@ -1,7 +1,11 @@
 use rustominoes::grpc::server::start_grpc;
+use rustominoes::lobby::GameManager;
 use tokio;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    start_grpc().await
+    let options = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+    let manager = GameManager::new(&options)?;
+
+    start_grpc(manager).await
 }
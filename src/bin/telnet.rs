@@ -2,15 +2,19 @@ use anyhow::Result;
 use async_trait::async_trait;
 use rustominoes::concurrent::*;
 use rustominoes::game::Move;
+use rustominoes::lobby::{GameManager, PLAYER_OPTIONS};
 use tokio::io::AsyncBufReadExt;
 use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
 use tokio::net::TcpListener;
 use tokio::net::TcpStream;
+use tokio::sync::broadcast::{error::RecvError, Receiver};
 
 struct TelnetPlayer {
     buf_reader: BufReader<TcpStream>,
     number: usize,
+    /// The line being read, kept whole when a turn times out halfway.
+    line: String,
 }
 
 impl TelnetPlayer {
@@ -18,6 +22,7 @@ impl TelnetPlayer {
         Box::new(TelnetPlayer {
             buf_reader,
             number: 0,
+            line: String::new(),
         })
     }
 }
@@ -31,13 +36,32 @@ impl RemotePlayer for TelnetPlayer {
 
     async fn read_move(&mut self) -> Move {
         loop {
-            let mut message = String::new();
-            if let Err(_) = self.buf_reader.read_line(&mut message).await {
-                continue;
+            match self.read_request().await {
+                Request::Move(mv) => return mv,
+                Request::Leave => return Move::Pass(self.number),
+                _ => {}
+            }
+        }
+    }
+
+    async fn read_request(&mut self) -> Request {
+        loop {
+            // The connection is closed or broken.
+            match self.buf_reader.read_line(&mut self.line).await {
+                Ok(0) | Err(_) => return Request::Leave,
+                Ok(_) => {}
+            }
+            let message = std::mem::take(&mut self.line);
+
+            match message.trim() {
+                "hint" => return Request::Hint,
+                "coach on" => return Request::Coach(true),
+                "coach off" => return Request::Coach(false),
+                _ => {}
             }
 
             if let Some(mv) = Move::parse_move(&message, self.number) {
-                return mv;
+                return Request::Move(mv);
             }
         }
     }
@@ -51,17 +75,124 @@ impl RemotePlayer for TelnetPlayer {
     }
 }
 
+/// Lists the tables until the player joins one with `join <table>`, or
+/// `join` for any table waiting for players. Options of `PLAYER_OPTIONS`
+/// after it, e.g. `join club wait=30 fill=beginner`, open a table of the
+/// player's own. `watch <table>` looks on at a table until its match is
+/// over.
+async fn enter(manager: GameManager, socket: TcpStream) -> Result<()> {
+    let mut buf_reader = BufReader::new(socket);
+
+    loop {
+        let mut tables = String::new();
+        for table in manager.list() {
+            let options = match table.options.as_str() {
+                "" => String::new(),
+                options => format!(", {}", options),
+            };
+            tables += &format!(
+                "table {}: {}/{} seated{}\n",
+                table.id, table.seated, table.seats, options
+            );
+        }
+        tables += "join <table> to sit at a table, join for any open one, list to look again\n";
+        tables += &format!(
+            "join <table> {}=... opens a table with those options\n",
+            PLAYER_OPTIONS.join("=... ")
+        );
+        tables += "bots are beginner, intermediate or expert, bot=<level> seats one\n";
+        tables += "watch <table> to look on at a table\n";
+        buf_reader.write_all(tables.as_bytes()).await?;
+
+        loop {
+            let mut line = String::new();
+            if buf_reader.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("list") => break,
+                Some("join") => {
+                    let (options, id): (Vec<_>, Vec<_>) =
+                        words.partition(|word| word.contains('='));
+                    let id = id.first().copied().unwrap_or_default();
+                    let options = options.join(" ");
+                    let open = manager.list().into_iter().find(|table| table.id == id);
+
+                    let refused = match (manager.check(&options), open) {
+                        (Err(error), _) => Some(error.to_string()),
+                        (Ok(()), Some(_)) if !options.is_empty() => {
+                            Some(format!("Table {} is already open", id))
+                        }
+                        (Ok(()), Some(table)) if table.full() => {
+                            Some(format!("Table {} is full", id))
+                        }
+                        _ => None,
+                    };
+                    if let Some(refused) = refused {
+                        buf_reader
+                            .write_all(format!("{}\n", refused).as_bytes())
+                            .await?;
+                        continue;
+                    }
+
+                    manager.join(id, &options, TelnetPlayer::new(buf_reader))?;
+                    return Ok(());
+                }
+                Some("watch") => match manager.watch(words.next().unwrap_or_default()) {
+                    Ok(spectators) => {
+                        watch(&mut buf_reader, spectators).await?;
+                        break;
+                    }
+                    Err(error) => {
+                        buf_reader
+                            .write_all(format!("{}\n", error).as_bytes())
+                            .await?;
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Shows a spectator every move at a table, with the win bar after it,
+/// until the match is over.
+async fn watch(
+    buf_reader: &mut BufReader<TcpStream>,
+    mut spectators: Receiver<Watch>,
+) -> Result<()> {
+    loop {
+        let (line, over) = match spectators.recv().await {
+            Ok(Watch::Chances(chances)) => (format!("{}\n", chances), false),
+            Ok(watch @ Watch::Over(_)) => (format!("{:?}\n", watch), true),
+            Ok(watch) => (format!("{:?}\n", watch), false),
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        buf_reader.write_all(line.as_bytes()).await?;
+
+        if over {
+            return Ok(());
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
+    let options = std::env::args().skip(1).collect::<Vec<_>>().join(" ");
+    let manager = GameManager::new(&options)?;
     let listener = TcpListener::bind("localhost:1234").await?;
-    let game_handler = start_game();
 
     loop {
         let (socket, _addr) = listener.accept().await?;
+        let manager = manager.clone();
 
-        if !game_handler.is_closed() {
-            let player = TelnetPlayer::new(BufReader::new(socket));
-            let _ = game_handler.send(player).await?;
-        }
+        tokio::spawn(async move {
+            if let Err(error) = enter(manager, socket).await {
+                eprintln!("{}", error);
+            }
+        });
     }
 }
@@ -0,0 +1,380 @@
+use crate::bots::player::{Bot, Strategy};
+use crate::bots::view::PlayerView;
+use crate::concurrent::{RemotePlayer, TimeLeft};
+use crate::game::{Domino, Move, Update};
+use crate::rules::{Rules, Variant};
+use anyhow::{anyhow, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Version of the engine protocol sent in the handshake.
+pub const PROTOCOL: u32 = 1;
+
+/// A bot running as a separate program, spoken to a line at a time over its
+/// standard input and output:
+///
+/// ```text
+/// > dominoes 1                   handshake, with the protocol version
+/// < name Some Bot                optional
+/// < ready
+/// > game block 4                 a match starts: variant and players
+/// > hand 2 6-6 5-4 3-1 ...       a hand is dealt: the engine's seat and tiles
+/// > play 0 left 6-3              a tile placed by seat 0 (`right` and
+///                                `branch <b>` too), as it lies on the line
+/// > draw 1                       seat 1 drew a tile
+/// > drawn 4-4                    the tile the engine drew, after its `draw`
+/// > pass 3                       seat 3 passed
+/// > time 5000                    milliseconds the engine has for the coming
+///                                move
+/// > go left 5-4, right 3-1, draw its turn, with the legal moves
+/// < move right 3-1               the answer, one of the legal moves
+/// ```
+///
+/// Lines the host does not expect are ignored. An engine that exits, does
+/// not answer in time or asks for an illegal move plays the first legal move
+/// instead, for the rest of the hand; it is started again for the next one.
+pub struct EngineStrategy {
+    command: String,
+    /// Time the engine has for the handshake and for every move. At a
+    /// timed table a move gets no more than the seat has left.
+    pub time: Duration,
+    /// Time the seat has for the coming move, when the table is timed.
+    time_left: Option<Duration>,
+    engine: Option<Engine>,
+    name: Option<String>,
+    /// Updates of the hand already sent.
+    sent: usize,
+    /// Tiles the engine knows it holds.
+    known: Vec<Domino>,
+}
+
+/// A running engine. Its output is read on a thread of its own so that
+/// reads can time out.
+struct Engine {
+    child: Child,
+    stdin: ChildStdin,
+    lines: Mutex<Receiver<String>>,
+}
+
+impl Engine {
+    fn spawn(command: &str) -> Result<Engine> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| anyhow!("No engine command".to_string()))?;
+
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let (sender, lines) = channel();
+
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(|line| line.ok()) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Engine {
+            child,
+            stdin,
+            lines: Mutex::new(lines),
+        })
+    }
+
+    fn send(&mut self, line: &str) -> Result<()> {
+        writeln!(self.stdin, "{}", line)?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    fn read(&mut self, deadline: Instant) -> Result<String> {
+        let left = deadline.saturating_duration_since(Instant::now());
+
+        match self.lines.get_mut().unwrap().recv_timeout(left) {
+            Ok(line) => Ok(line.trim().to_string()),
+            Err(RecvTimeoutError::Timeout) => {
+                Err(anyhow!("Engine did not answer in time".to_string()))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("Engine exited".to_string())),
+        }
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+impl EngineStrategy {
+    /// The engine is started by `command`, a program followed by its
+    /// arguments, when the first hand is dealt.
+    pub fn new(command: &str) -> EngineStrategy {
+        EngineStrategy {
+            command: command.to_string(),
+            time: Duration::from_secs(5),
+            time_left: None,
+            engine: None,
+            name: None,
+            sent: 0,
+            known: Vec::new(),
+        }
+    }
+
+    /// The name the engine gave in the handshake.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    fn start(&mut self, view: &PlayerView) -> Result<()> {
+        let mut engine = Engine::spawn(&self.command)?;
+        let deadline = Instant::now() + self.time;
+
+        engine.send(&format!("dominoes {}", PROTOCOL))?;
+        let mut name = None;
+        loop {
+            let line = engine.read(deadline)?;
+            match line.split_once(' ') {
+                Some(("name", rest)) => name = Some(rest.trim().to_string()),
+                _ if line == "ready" => break,
+                _ => {}
+            }
+        }
+
+        engine.send(&format!(
+            "game {} {}",
+            variant_name(view.game.rules.variant),
+            view.game.n_players()
+        ))?;
+
+        self.name = name;
+        self.engine = Some(engine);
+        Ok(())
+    }
+
+    /// Sends what happened since the engine's last turn, then asks it for a
+    /// move.
+    fn ask(&mut self, view: &PlayerView) -> Result<Move> {
+        let engine = self
+            .engine
+            .as_mut()
+            .ok_or_else(|| anyhow!("Engine not running".to_string()))?;
+
+        let mut drawn = view
+            .hand()
+            .iter()
+            .filter(|piece| !self.known.contains(piece))
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        for (player, update) in &view.history[self.sent.min(view.history.len())..] {
+            engine.send(&update_line(*player, update))?;
+
+            if let (Update::Draw, true) = (update, *player == view.seat) {
+                if let Some(piece) = drawn.next() {
+                    engine.send(&format!("drawn {}", piece))?;
+                }
+            }
+        }
+        self.sent = view.history.len();
+        self.known = view.hand().to_vec();
+
+        let moves = view.legal_moves();
+        let legal = moves
+            .iter()
+            .map(|move_| move_line(view, move_))
+            .collect::<Vec<_>>();
+
+        let time = match self.time_left.take() {
+            Some(time_left) => time_left.min(self.time),
+            None => self.time,
+        };
+        engine.send(&format!("time {}", time.as_millis()))?;
+        engine.send(&format!("go {}", legal.join(", ")))?;
+
+        let deadline = Instant::now() + time;
+        let answer = loop {
+            if let Some(("move", answer)) = engine.read(deadline)?.split_once(' ') {
+                break answer.split_whitespace().collect::<Vec<_>>().join(" ");
+            }
+        };
+
+        legal
+            .iter()
+            .position(|line| *line == answer)
+            .map(|position| moves[position])
+            .ok_or_else(|| anyhow!("Engine asked for an illegal move: {}", answer))
+    }
+
+    fn stop(&mut self, error: anyhow::Error) {
+        eprintln!("{}: {}", self.command, error);
+        self.engine = None;
+    }
+}
+
+impl Strategy for EngineStrategy {
+    fn deal(&mut self, view: &PlayerView) {
+        if self.engine.is_none() {
+            if let Err(error) = self.start(view) {
+                return self.stop(error);
+            }
+        }
+
+        self.sent = 0;
+        self.known = view.hand().to_vec();
+
+        let tiles = view
+            .hand()
+            .iter()
+            .map(|piece| format!(" {}", piece))
+            .collect::<String>();
+        let line = format!("hand {}{}", view.seat, tiles);
+
+        if let Some(Err(error)) = self.engine.as_mut().map(|engine| engine.send(&line)) {
+            self.stop(error);
+        }
+    }
+
+    fn turn(&mut self, time_left: TimeLeft) {
+        self.time_left = Some(time_left.move_);
+    }
+
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        let moves = view.legal_moves();
+
+        match self.ask(view) {
+            Ok(move_) => move_,
+            Err(error) => {
+                if self.engine.is_some() {
+                    self.stop(error);
+                }
+                moves[0]
+            }
+        }
+    }
+}
+
+fn variant_name(variant: Variant) -> &'static str {
+    match variant {
+        Variant::Block => "block",
+        Variant::ChickenFoot => "chicken-foot",
+        Variant::Brazilian => "brazilian",
+        Variant::Caribbean => "caribbean",
+        Variant::Sniff => "sniff",
+        Variant::Matador => "matador",
+        Variant::Bergen => "bergen",
+    }
+}
+
+fn update_line(player: usize, update: &Update) -> String {
+    match *update {
+        Update::Left(piece) => format!("play {} left {}", player, piece),
+        Update::Right(piece) => format!("play {} right {}", player, piece),
+        Update::Branch(branch, piece) => format!("play {} branch {} {}", player, branch, piece),
+        Update::Draw => format!("draw {}", player),
+        Update::Skip => format!("pass {}", player),
+    }
+}
+
+/// A move of the engine's seat as it is offered and answered.
+fn move_line(view: &PlayerView, move_: &Move) -> String {
+    let piece = view
+        .piece(move_)
+        .map(|piece| piece.to_string())
+        .unwrap_or_default();
+
+    match *move_ {
+        Move::Left(..) => format!("left {}", piece),
+        Move::Right(..) => format!("right {}", piece),
+        Move::Branch(_, branch, _) => format!("branch {} {}", branch, piece),
+        Move::Draw(_) => "draw".to_string(),
+        Move::Pass(_) => "pass".to_string(),
+    }
+}
+
+/// Seats an engine at a network table.
+pub fn engine_bot(rules: Rules, n_players: i32, command: &str) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, EngineStrategy::new(command)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::heuristics::RandomStrategy;
+    use crate::bots::playout::play_hand;
+    use crate::game::Game;
+    use crate::log::GameLog;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// An engine that always plays the first move it is offered.
+    const FIRST: &str = "while read -r line; do
+  case \"$line\" in
+    dominoes*) echo 'name first'; echo ready;;
+    go*) moves=${line#go }; echo \"move ${moves%%,*}\";;
+  esac
+done
+";
+
+    fn play(command: &str) -> GameLog {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut game = Game::deal_with(Rules::block(), 2, 0, &mut rng);
+        let mut engine = EngineStrategy::new(command);
+        engine.time = Duration::from_millis(500);
+
+        let mut strategies: Vec<Box<dyn Strategy>> =
+            vec![Box::new(engine), Box::new(RandomStrategy::new(1))];
+        let mut log = GameLog::new();
+        play_hand(&mut game, 0, &mut strategies, &mut log);
+
+        log
+    }
+
+    #[test]
+    fn test_engine_plays_a_hand() {
+        let name = format!("rustominoes-first-engine-{}.sh", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, FIRST).unwrap();
+        let command = format!("sh {}", path.display());
+
+        let mut engine = EngineStrategy::new(&command);
+        let mut rng = StdRng::seed_from_u64(5);
+        let game = Game::deal_with(Rules::block(), 2, 0, &mut rng);
+        let view = PlayerView::new(Rules::block(), 2, 0, 0, game.players[0].clone());
+
+        engine.deal(&view);
+        assert_eq!(Some("first"), engine.name());
+        assert_eq!(view.legal_moves()[0], engine.choose(&view));
+
+        engine.turn(TimeLeft {
+            move_: Duration::from_millis(300),
+            clock: None,
+        });
+        assert_eq!(view.legal_moves()[0], engine.choose(&view));
+
+        let log = play(&command);
+        assert!(!log.events.is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_broken_engines_fall_back() {
+        // `true` exits at once and `cat` never says it is ready.
+        for command in ["true", "cat", "no-such-engine"] {
+            let log = play(command);
+            assert!(!log.events.is_empty());
+        }
+    }
+}
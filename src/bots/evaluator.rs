@@ -0,0 +1,298 @@
+use crate::analysis::{replay, Step};
+use crate::bots::player::{Bot, Strategy};
+use crate::bots::playout::value;
+use crate::bots::view::PlayerView;
+use crate::concurrent::RemotePlayer;
+use crate::game::{Domino, Move, HIDDEN};
+use crate::log::GameLog;
+use crate::rules::Rules;
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::path::Path;
+
+/// What the evaluator looks at in the position a move leaves behind: a
+/// constant 1, the points of the tile got rid of, the different numbers left
+/// in hand, the open ends the player still holds a tile for, the ones the
+/// partner may hold a tile for, the ones the next player is known to hold
+/// nothing for, and whether the tile is a double, as those are hard to place
+/// later.
+pub const FEATURES: [&str; 7] = [
+    "bias", "pips", "suits", "control", "partner", "opponent", "double",
+];
+
+/// A weight for each of the `FEATURES`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights(pub [f64; FEATURES.len()]);
+
+impl Default for Weights {
+    fn default() -> Weights {
+        Weights([0.0, 1.0, 0.5, 0.5, 0.3, 0.5, 0.3])
+    }
+}
+
+impl Weights {
+    /// Reads weights written as `name value` lines, as `Display` writes them.
+    /// Features left out keep their default weight.
+    pub fn parse(text: &str) -> Result<Weights> {
+        let mut weights = Weights::default();
+
+        for line in text.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (name, value) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| anyhow!("Cannot read weight {}", line))?;
+            let feature = FEATURES
+                .iter()
+                .position(|feature| *feature == name)
+                .ok_or_else(|| anyhow!("Unknown feature {}", name))?;
+            weights.0[feature] = value.trim().parse()?;
+        }
+
+        Ok(weights)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Weights> {
+        Weights::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(std::fs::write(path, self.to_string())?)
+    }
+
+    pub fn score(&self, features: &[f64; FEATURES.len()]) -> f64 {
+        self.0
+            .iter()
+            .zip(features)
+            .map(|(weight, x)| weight * x)
+            .sum()
+    }
+}
+
+impl fmt::Display for Weights {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, weight) in FEATURES.iter().zip(self.0) {
+            writeln!(f, "{} {}", name, weight)?;
+        }
+        Ok(())
+    }
+}
+
+/// The features of the position the seat's move would leave, each between
+/// 0 and 1.
+pub fn features(view: &PlayerView, move_: &Move) -> [f64; FEATURES.len()] {
+    let rules = &view.game.rules;
+    let n_players = view.game.n_players() as usize;
+    let seat = view.seat;
+    let piece = view.piece(move_);
+
+    let mut after = view.game.clone();
+    after.next = seat as i32;
+    let _ = after.play(move_);
+
+    let hand = after.players[seat]
+        .iter()
+        .filter(|tile| **tile != HIDDEN)
+        .copied()
+        .collect::<Vec<_>>();
+    let ends = after
+        .open_ends()
+        .into_iter()
+        .map(|(_, number)| number)
+        .collect::<Vec<_>>();
+    let share = |holds: &dyn Fn(i32) -> bool| {
+        ends.iter().filter(|end| holds(**end)).count() as f64 / ends.len().max(1) as f64
+    };
+    let any_fits = |tiles: &[Domino], end: i32| tiles.iter().any(|tile| fits(rules, tile, end));
+
+    let mut numbers = hand
+        .iter()
+        .flat_map(|tile| [tile.0, tile.1])
+        .collect::<Vec<_>>();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let partner = match rules.partnerships && n_players == 4 {
+        true => {
+            let candidates = view.tracker.candidates((seat + 2) % 4);
+            share(&|end| any_fits(&candidates, end))
+        }
+        false => 0.0,
+    };
+    let next = view.tracker.candidates((seat + 1) % n_players);
+
+    [
+        1.0,
+        piece
+            .map(|piece| rules.pip_value(&piece) as f64 / (2 * rules.max_pip).max(1) as f64)
+            .unwrap_or(0.0),
+        numbers.len() as f64 / (rules.max_pip + 1) as f64,
+        share(&|end| any_fits(&hand, end)),
+        partner,
+        share(&|end| !any_fits(&next, end)),
+        match piece {
+            Some(piece) if piece.is_double() => 1.0,
+            _ => 0.0,
+        },
+    ]
+}
+
+fn fits(rules: &Rules, tile: &Domino, end: i32) -> bool {
+    rules.is_wild(tile) || rules.matches(tile.0, end) || rules.matches(tile.1, end)
+}
+
+/// Plays the move whose position scores best under the weights.
+pub struct EvaluatorStrategy {
+    pub weights: Weights,
+}
+
+impl EvaluatorStrategy {
+    pub fn new(weights: Weights) -> EvaluatorStrategy {
+        EvaluatorStrategy { weights }
+    }
+}
+
+impl Strategy for EvaluatorStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        let moves = view.legal_moves();
+
+        moves
+            .iter()
+            .map(|move_| (*move_, self.weights.score(&features(view, move_))))
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(move_, _)| move_)
+            .unwrap_or(Move::Pass(view.seat))
+    }
+}
+
+pub fn evaluator_bot(rules: Rules, n_players: i32, weights: Weights) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, EvaluatorStrategy::new(weights)))
+}
+
+/// A move made with a choice of moves, and how the hand went for the side
+/// that made it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub features: [f64; FEATURES.len()],
+    pub result: f64,
+}
+
+/// The samples of every move made with a choice in a recorded match played
+/// under `rules`.
+pub fn samples(rules: &Rules, log: &GameLog) -> Result<Vec<Sample>> {
+    let mut samples = Vec::new();
+    let mut pending = Vec::new();
+
+    replay(rules, log, |replay, step| match step {
+        Step::Move(move_, _) => {
+            let seat = move_.player();
+            let view = &replay.views[seat];
+            let moves = replay.game.legal_moves(seat);
+
+            // Compared with the moves it could have made, so that the
+            // weights are not fitted to how good the hand was to begin with.
+            if moves.len() > 1 {
+                let mut features = features(view, move_);
+                for other in &moves {
+                    let others = self::features(view, other);
+                    for (x, y) in features.iter_mut().zip(others).skip(1) {
+                        *x -= y / moves.len() as f64;
+                    }
+                }
+                pending.push((seat, features));
+            }
+        }
+        Step::Over(outcome) => {
+            for (seat, features) in pending.drain(..) {
+                let result = value(&replay.game, outcome, seat) as f64;
+                samples.push(Sample { features, result });
+            }
+        }
+    })?;
+
+    Ok(samples)
+}
+
+/// Mean squared error of the weights predicting the results.
+pub fn error(weights: &Weights, samples: &[Sample]) -> f64 {
+    let total = samples
+        .iter()
+        .map(|sample| (weights.score(&sample.features) - sample.result).powi(2))
+        .sum::<f64>();
+    total / samples.len().max(1) as f64
+}
+
+/// Fits the weights to predict the result of the hand from the position
+/// each move left, by gradient descent on the squared error.
+pub fn fit(samples: &[Sample], start: Weights, epochs: usize, rate: f64) -> Weights {
+    let mut weights = start;
+    let n = samples.len().max(1) as f64;
+
+    for _ in 0..epochs {
+        let mut gradient = [0.0; FEATURES.len()];
+        for sample in samples {
+            let error = weights.score(&sample.features) - sample.result;
+            for (slope, x) in gradient.iter_mut().zip(sample.features) {
+                *slope += 2.0 * error * x / n;
+            }
+        }
+
+        for (weight, slope) in weights.0.iter_mut().zip(gradient) {
+            *weight -= rate * slope;
+        }
+    }
+
+    weights
+}
+
+/// Tunes weights on recorded self-play matches.
+pub fn train(rules: &Rules, logs: &[GameLog], epochs: usize) -> Result<Weights> {
+    let mut all = Vec::new();
+    for log in logs {
+        all.extend(samples(rules, log)?);
+    }
+
+    Ok(fit(&all, Weights::default(), epochs, 0.1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+
+    #[test]
+    fn test_weights_file() {
+        let weights = Weights([0.5, -1.0, 2.0, 0.0, 0.25, 1.5, -0.125]);
+
+        assert_eq!(weights, Weights::parse(&weights.to_string()).unwrap());
+        assert_eq!(
+            Weights::default().0[1..],
+            Weights::parse("# only the bias\nbias 3").unwrap().0[1..]
+        );
+        assert!(Weights::parse("luck 1").is_err());
+    }
+
+    #[test]
+    fn test_training_on_self_play() {
+        let arena = Arena::from_options("bots=random,random block hands=1").unwrap();
+        let logs = (0..30)
+            .map(|game| arena.record(game).log)
+            .collect::<Vec<_>>();
+
+        let mut all = Vec::new();
+        for log in &logs {
+            all.extend(samples(&arena.rules, log).unwrap());
+        }
+        let weights = train(&arena.rules, &logs, 300).unwrap();
+
+        assert!(all.len() > 100);
+        assert!(error(&weights, &all) < error(&Weights::default(), &all));
+
+        let mut arena = Arena::from_options("bots=evaluator,random games=40 hands=1").unwrap();
+        arena.threads = 1;
+        let report = arena.run();
+        assert!(report.wins[0] > report.wins[1]);
+    }
+}
@@ -0,0 +1,159 @@
+use crate::bots::player::{Bot, Strategy};
+use crate::bots::view::PlayerView;
+use crate::concurrent::RemotePlayer;
+use crate::game::{Domino, Move};
+use crate::rules::Rules;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Plays any legal move.
+pub struct RandomStrategy {
+    rng: StdRng,
+}
+
+impl RandomStrategy {
+    pub fn new(seed: u64) -> RandomStrategy {
+        RandomStrategy {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Strategy for RandomStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        let moves = view.legal_moves();
+        *moves
+            .choose(&mut self.rng)
+            .unwrap_or(&Move::Pass(view.seat))
+    }
+}
+
+/// Gets rid of the heaviest tile it can play.
+pub struct GreedyStrategy;
+
+impl Strategy for GreedyStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        best_by(view, |view, piece| view.game.rules.pip_value(&piece))
+    }
+}
+
+/// Keeps as many different numbers in hand as it can, so that it is rarely
+/// left without a tile for an end. Heavier tiles go first on ties.
+pub struct VarietyStrategy;
+
+impl Strategy for VarietyStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        best_by(view, |view, piece| {
+            let mut rest = view.hand().to_vec();
+            if let Some(pos) = rest.iter().position(|other| *other == piece) {
+                rest.remove(pos);
+            }
+
+            suits(&rest) * 1000 + piece.pips()
+        })
+    }
+}
+
+/// Numbers showing on the given tiles.
+fn suits(tiles: &[Domino]) -> i32 {
+    let mut numbers = tiles
+        .iter()
+        .flat_map(|domino| [domino.0, domino.1])
+        .collect::<Vec<_>>();
+    numbers.sort();
+    numbers.dedup();
+    numbers.len() as i32
+}
+
+/// The legal move whose tile scores highest, keeping the first on ties.
+/// Draws and passes are only ever legal on their own.
+fn best_by(view: &PlayerView, score: impl Fn(&PlayerView, Domino) -> i32) -> Move {
+    let moves = view.legal_moves();
+    let mut best: Option<(i32, Move)> = None;
+
+    for move_ in moves.iter() {
+        let value = match view.piece(move_) {
+            Some(piece) => score(view, piece),
+            None => i32::MIN,
+        };
+
+        if best.map(|(top, _)| value > top).unwrap_or(true) {
+            best = Some((value, *move_));
+        }
+    }
+
+    best.map(|(_, move_)| move_)
+        .unwrap_or(Move::Pass(view.seat))
+}
+
+pub fn random_bot(rules: Rules, n_players: i32, seed: u64) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, RandomStrategy::new(seed)))
+}
+
+pub fn greedy_bot(rules: Rules, n_players: i32) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, GreedyStrategy))
+}
+
+pub fn variety_bot(rules: Rules, n_players: i32) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, VarietyStrategy))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::concurrent::play_match;
+
+    #[test]
+    fn test_greedy_opens_heaviest() {
+        let mut view = PlayerView::new(
+            Rules::block(),
+            2,
+            0,
+            0,
+            vec![Domino(1, 0), Domino(6, 5), Domino(3, 3)],
+        );
+        view.game.next = 0;
+
+        assert_eq!(Move::Left(0, 1), GreedyStrategy.choose(&view));
+    }
+
+    #[test]
+    fn test_variety_keeps_suits() {
+        let mut view = PlayerView::new(
+            Rules::block(),
+            2,
+            0,
+            0,
+            vec![Domino(6, 5), Domino(6, 1), Domino(1, 0)],
+        );
+        view.game.next = 0;
+        view.game.board = vec![Domino(6, 3)];
+
+        assert_eq!(Move::Left(0, 0), GreedyStrategy.choose(&view));
+        assert_eq!(Move::Left(0, 1), VarietyStrategy.choose(&view));
+    }
+
+    #[tokio::test]
+    async fn test_bots_finish_a_match() {
+        for options in [
+            "block target=50",
+            "chicken-foot hands=2",
+            "chicken-foot duplicate=1",
+            "sniff target=61",
+        ] {
+            let rules = Rules::from_options(options).unwrap();
+            let players = vec![
+                random_bot(rules.clone(), 4, 7),
+                greedy_bot(rules.clone(), 4),
+                variety_bot(rules.clone(), 4),
+                random_bot(rules.clone(), 4, 11),
+            ];
+
+            let match_ = play_match(players, rules).await;
+
+            assert!(match_.is_over());
+            assert!(match_.winner().is_some());
+        }
+    }
+}
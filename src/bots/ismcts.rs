@@ -0,0 +1,266 @@
+use crate::bots::player::{Bot, Strategy};
+use crate::bots::playout::{random_playout, value};
+use crate::bots::view::PlayerView;
+use crate::concurrent::RemotePlayer;
+use crate::game::{Domino, End, Game, Move};
+use crate::rules::Rules;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::time::{Duration, Instant};
+
+/// A move told apart by the tile and end rather than by the position of the
+/// tile in a hand, which changes from one sampled deal to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Place(End, Domino),
+    Draw,
+    Pass,
+}
+
+impl Action {
+    fn of(game: &Game, move_: &Move) -> Action {
+        let (end, player, pos) = match *move_ {
+            Move::Left(player, pos) => (End::Left, player, pos),
+            Move::Right(player, pos) => (End::Right, player, pos),
+            Move::Branch(player, branch, pos) => (End::Branch(branch), player, pos),
+            Move::Draw(_) => return Action::Draw,
+            Move::Pass(_) => return Action::Pass,
+        };
+
+        Action::Place(end, game.players[player][pos].normalized())
+    }
+}
+
+#[derive(Debug)]
+struct Node {
+    /// The move leading here and the seat that made it.
+    action: Option<(usize, Action)>,
+    children: Vec<usize>,
+    visits: u32,
+    /// Times this node was legal when its parent was visited.
+    available: u32,
+    reward: f64,
+}
+
+impl Node {
+    fn new(action: Option<(usize, Action)>) -> Node {
+        Node {
+            action,
+            children: Vec::new(),
+            visits: 0,
+            available: 1,
+            reward: 0.0,
+        }
+    }
+}
+
+/// Single observer information set Monte Carlo tree search. Every iteration
+/// samples a deal of the unseen tiles and walks one tree shared by all of
+/// them, so statistics are gathered for what the seat knows rather than for
+/// any single deal. Each node is scored for the seat that moved into it.
+pub struct IsmctsStrategy {
+    seed: u64,
+    /// Iterations per decision, split between the threads.
+    pub iterations: usize,
+    /// Weight of the exploration term in the selection formula.
+    pub exploration: f64,
+    /// Independent trees searched at once, their root statistics summed.
+    pub threads: usize,
+    /// Stops searching early once this much time has gone by.
+    pub time: Option<Duration>,
+}
+
+impl IsmctsStrategy {
+    pub fn new(seed: u64) -> IsmctsStrategy {
+        IsmctsStrategy {
+            seed,
+            iterations: 1000,
+            exploration: 0.7,
+            threads: 1,
+            time: None,
+        }
+    }
+
+    fn search(&self, view: &PlayerView, iterations: usize, seed: u64) -> Vec<Node> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut tree = vec![Node::new(None)];
+        let started = Instant::now();
+
+        for _ in 0..iterations {
+            if let Some(time) = self.time {
+                if started.elapsed() >= time {
+                    break;
+                }
+            }
+
+            let mut game = view.sample(&mut rng);
+            game.next = view.seat as i32;
+            let mut path = vec![0];
+            let mut node = 0;
+
+            while game.outcome().is_none() {
+                let player = game.next as usize;
+                let moves = game
+                    .legal_moves(player)
+                    .into_iter()
+                    .map(|move_| (move_, Action::of(&game, &move_)))
+                    .collect::<Vec<_>>();
+
+                let legal = tree[node]
+                    .children
+                    .iter()
+                    .copied()
+                    .filter(|child| {
+                        let (seat, action) = tree[*child].action.unwrap();
+                        seat == player && moves.iter().any(|(_, other)| *other == action)
+                    })
+                    .collect::<Vec<_>>();
+
+                let untried = moves
+                    .iter()
+                    .filter(|(_, action)| {
+                        !legal
+                            .iter()
+                            .any(|child| tree[*child].action == Some((player, *action)))
+                    })
+                    .collect::<Vec<_>>();
+
+                for child in &legal {
+                    tree[*child].available += 1;
+                }
+
+                if let Some((move_, action)) = untried.choose(&mut rng) {
+                    let _ = game.play(move_);
+                    tree.push(Node::new(Some((player, *action))));
+                    let child = tree.len() - 1;
+                    tree[node].children.push(child);
+                    path.push(child);
+                    break;
+                }
+
+                let child = *legal
+                    .iter()
+                    .max_by(|a, b| {
+                        self.ucb(&tree[**a])
+                            .partial_cmp(&self.ucb(&tree[**b]))
+                            .unwrap()
+                    })
+                    .unwrap();
+                let (_, action) = tree[child].action.unwrap();
+                let (move_, _) = moves.iter().find(|(_, other)| *other == action).unwrap();
+                let _ = game.play(move_);
+
+                path.push(child);
+                node = child;
+            }
+
+            let outcome = random_playout(&mut game, &mut rng);
+
+            for node in path {
+                let seat = tree[node].action.map_or(view.seat, |(seat, _)| seat);
+                tree[node].visits += 1;
+                tree[node].reward += reward(value(&game, outcome, seat));
+            }
+        }
+
+        tree
+    }
+
+    fn ucb(&self, node: &Node) -> f64 {
+        let visits = node.visits as f64;
+        node.reward / visits + self.exploration * ((node.available as f64).ln() / visits).sqrt()
+    }
+}
+
+/// Squashes a hand's value into 0..1, keeping its sign and, to a lesser
+/// degree, its size.
+fn reward(value: i32) -> f64 {
+    let value = value as f64;
+    0.5 + 0.5 * value / (value.abs() + 10.0)
+}
+
+impl Strategy for IsmctsStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        let moves = view.legal_moves();
+        if moves.len() == 1 {
+            return moves[0];
+        }
+
+        let threads = self.threads.max(1);
+        let iterations = self.iterations / threads + 1;
+        self.seed = self.seed.wrapping_add(1);
+
+        let trees = std::thread::scope(|scope| {
+            let searches = (0..threads)
+                .map(|i| {
+                    let seed = self.seed.wrapping_mul(31).wrapping_add(i as u64);
+                    let this = &*self;
+                    scope.spawn(move || this.search(view, iterations, seed))
+                })
+                .collect::<Vec<_>>();
+
+            searches
+                .into_iter()
+                .map(|search| search.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+
+        let visits = |move_: &Move| -> u32 {
+            let action = Action::of(&view.game, move_);
+            trees
+                .iter()
+                .flat_map(|tree| tree[0].children.iter().map(move |child| &tree[*child]))
+                .filter(|node| node.action == Some((view.seat, action)))
+                .map(|node| node.visits)
+                .sum()
+        };
+
+        *moves.iter().max_by_key(|move_| visits(move_)).unwrap()
+    }
+}
+
+pub fn ismcts_bot(rules: Rules, n_players: i32, seed: u64) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, IsmctsStrategy::new(seed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::heuristics::RandomStrategy;
+    use crate::bots::playout::{head_to_head, play_hand};
+    use crate::log::GameLog;
+
+    #[test]
+    fn test_ismcts_beats_random() {
+        let hands = 60;
+        let wins = head_to_head(&Rules::block(), hands, 3, |hand| {
+            let mut ismcts = IsmctsStrategy::new(hand);
+            ismcts.iterations = 300;
+            ismcts.threads = 2;
+
+            [Box::new(ismcts), Box::new(RandomStrategy::new(hand))]
+        });
+
+        // At least three hands in four, where random play would win half.
+        assert!(wins[0] * 4 >= hands as usize * 3, "won {:?}", wins);
+        assert!(wins[0] >= 2 * wins[1]);
+    }
+
+    #[test]
+    fn test_ismcts_partnerships() {
+        let rules = Rules::caribbean();
+        let mut strategies: Vec<Box<dyn Strategy>> = (0..4)
+            .map(|seat| {
+                let mut ismcts = IsmctsStrategy::new(seat);
+                ismcts.iterations = 50;
+                Box::new(ismcts) as Box<dyn Strategy>
+            })
+            .collect();
+
+        let mut game = Game::deal(rules, 4, 0);
+        play_hand(&mut game, 0, &mut strategies, &mut GameLog::new());
+
+        assert!(game.outcome().is_some());
+    }
+}
@@ -0,0 +1,215 @@
+use crate::bots::engine::EngineStrategy;
+use crate::bots::evaluator::{EvaluatorStrategy, Weights};
+use crate::bots::heuristics::GreedyStrategy;
+use crate::bots::ismcts::IsmctsStrategy;
+use crate::bots::player::{Bot, Strategy};
+use crate::bots::script::ScriptStrategy;
+use crate::bots::view::PlayerView;
+use crate::concurrent::{Clock, Fill, RemotePlayer, Timeout};
+use crate::game::Move;
+use crate::rules::Rules;
+use anyhow::{anyhow, Result};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::time::Duration;
+
+/// How long the expert thinks about a move unless told otherwise.
+pub const THINK: Duration = Duration::from_secs(2);
+
+/// How strong a bot plays.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Level {
+    /// Plays its heaviest tile, but often just any tile.
+    Beginner,
+    /// Weighs up what each move leaves behind.
+    Intermediate,
+    /// Searches the game tree.
+    Expert,
+}
+
+impl Level {
+    pub fn parse(name: &str) -> Option<Level> {
+        match name {
+            "beginner" | "easy" => Some(Level::Beginner),
+            "intermediate" | "medium" => Some(Level::Intermediate),
+            "expert" | "hard" => Some(Level::Expert),
+            _ => None,
+        }
+    }
+
+    /// A strategy of this level. `think` limits the time spent on a move by
+    /// the levels that search.
+    pub fn strategy(&self, seed: u64, think: Option<Duration>) -> Box<dyn Strategy> {
+        match self {
+            Level::Beginner => Box::new(BeginnerStrategy::new(seed)),
+            Level::Intermediate => Box::new(EvaluatorStrategy::new(Weights::default())),
+            Level::Expert => {
+                let mut strategy = IsmctsStrategy::new(seed);
+                strategy.iterations = 20_000;
+                strategy.time = Some(think.unwrap_or(THINK));
+                Box::new(strategy)
+            }
+        }
+    }
+}
+
+/// Plays like the greedy bot, except that now and then it makes another
+/// move on purpose.
+pub struct BeginnerStrategy {
+    rng: StdRng,
+    /// Share of the moves made at random.
+    pub mistakes: f64,
+}
+
+impl BeginnerStrategy {
+    pub fn new(seed: u64) -> BeginnerStrategy {
+        BeginnerStrategy {
+            rng: StdRng::seed_from_u64(seed),
+            mistakes: 0.3,
+        }
+    }
+}
+
+impl Strategy for BeginnerStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        let moves = view.legal_moves();
+
+        match self.rng.gen_bool(self.mistakes) {
+            true => *moves
+                .choose(&mut self.rng)
+                .unwrap_or(&Move::Pass(view.seat)),
+            false => GreedyStrategy.choose(view),
+        }
+    }
+}
+
+/// A seat of the given level at a network table.
+pub fn level_bot(
+    level: Level,
+    rules: Rules,
+    n_players: i32,
+    seed: u64,
+    think: Option<Duration>,
+) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, level.strategy(seed, think)))
+}
+
+/// What the options of a server set up for a table besides the rules.
+pub struct TableOptions {
+    pub rules: Rules,
+    /// Bots seated before anybody joins.
+    pub bots: Vec<Box<dyn RemotePlayer>>,
+    /// Bots seated when nobody comes in time.
+    pub fill: Option<Fill>,
+    pub clock: Clock,
+}
+
+/// Reads the table options of a server, taking out the seats to be filled
+/// with bots before anybody joins: `bot=<level>`, `engine=<program>` and
+/// `script=<file>` each seat one, and `think=<seconds>` limits how long the
+/// expert bots think. `wait=<seconds>` seats bots in the seats still empty
+/// after that long, of the level `fill=<level>`, intermediate unless given.
+/// `move-time=<seconds>` limits each move and `clock=<seconds>` the moves of
+/// each player over the match; `timeout=pass|bot|forfeit` says what comes of
+/// running out of time.
+pub fn table_options(options: &str, n_players: i32) -> Result<TableOptions> {
+    let mut seats = Vec::new();
+    let mut think = None;
+    let mut wait = None;
+    let mut fill = Level::Intermediate;
+    let mut clock = Clock::default();
+    let mut table = Vec::new();
+
+    for word in options.split_whitespace() {
+        match word.split_once('=') {
+            Some(("think", value)) => think = Some(seconds(value)?),
+            Some(("wait", value)) => wait = Some(seconds(value)?),
+            Some(("move-time", value)) => clock.per_move = Some(seconds(value)?),
+            Some(("clock", value)) => clock.per_match = Some(seconds(value)?),
+            Some(("timeout", policy)) => {
+                clock.timeout =
+                    Timeout::parse(policy).ok_or_else(|| anyhow!("Unknown timeout {}", policy))?
+            }
+            Some(("fill", level)) => {
+                fill = Level::parse(level).ok_or_else(|| anyhow!("Unknown level {}", level))?
+            }
+            Some((kind @ ("bot" | "engine" | "script"), value)) => seats.push((kind, value)),
+            _ => table.push(word),
+        }
+    }
+
+    let rules = Rules::from_options(&table.join(" "))?;
+    let mut bots = Vec::new();
+
+    for (seed, (kind, value)) in seats.into_iter().enumerate() {
+        let strategy: Box<dyn Strategy> = match kind {
+            "engine" => Box::new(EngineStrategy::new(value)),
+            "script" => Box::new(ScriptStrategy::new(value)),
+            _ => Level::parse(value)
+                .ok_or_else(|| anyhow!("Unknown level {}", value))?
+                .strategy(seed as u64, think),
+        };
+        bots.push(Box::new(Bot::new(rules.clone(), n_players, strategy)) as Box<dyn RemotePlayer>);
+    }
+
+    let fill = wait.map(|wait| {
+        let rules = rules.clone();
+        Fill {
+            wait,
+            bot: Box::new(move || level_bot(fill, rules.clone(), n_players, rand::random(), think)),
+        }
+    });
+
+    Ok(TableOptions {
+        rules,
+        bots,
+        fill,
+        clock,
+    })
+}
+
+/// Reads a time given in seconds, which may have a fraction.
+fn seconds(value: &str) -> Result<Duration> {
+    Duration::try_from_secs_f64(value.parse()?).map_err(|_| anyhow!("Invalid time {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arena::Arena;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(Some(Level::Expert), Level::parse("hard"));
+        assert!(Level::parse("grandmaster").is_none());
+
+        let table = table_options("bot=beginner think=0.5 block target=20 bot=expert", 4).unwrap();
+        assert_eq!(2, table.bots.len());
+        assert_eq!(crate::rules::Goal::Target(20), table.rules.goal);
+        assert!(table.fill.is_none());
+        assert!(table_options("bot=grandmaster", 4).is_err());
+        assert!(table_options("bot=expert think=-1", 4).is_err());
+
+        let table = table_options("wait=1.5 fill=expert", 4).unwrap();
+        assert_eq!(
+            Some(Duration::from_millis(1500)),
+            table.fill.map(|fill| fill.wait)
+        );
+        assert!(table_options("wait=1 fill=grandmaster", 4).is_err());
+        assert!(table_options("wait=-1", 4).is_err());
+
+        let table = table_options("move-time=20 clock=300 timeout=bot", 4).unwrap();
+        assert_eq!(Some(Duration::from_secs(20)), table.clock.per_move);
+        assert_eq!(Some(Duration::from_secs(300)), table.clock.per_match);
+        assert_eq!(Timeout::Bot, table.clock.timeout);
+        assert!(table_options("move-time=5 timeout=resign", 4).is_err());
+        assert!(table_options("clock=nan", 4).is_err());
+        assert!(table_options("move-time=inf", 4).is_err());
+
+        let mut arena = Arena::from_options("bots=intermediate,beginner games=60 hands=1").unwrap();
+        arena.threads = 1;
+        let report = arena.run();
+        assert!(report.wins[0] > report.wins[1]);
+    }
+}
@@ -0,0 +1,122 @@
+pub mod engine;
+pub mod evaluator;
+pub mod heuristics;
+pub mod ismcts;
+pub mod levels;
+pub mod pimc;
+pub mod player;
+pub mod playout;
+pub mod script;
+pub mod solver;
+pub mod tablebase;
+pub mod view;
+
+use anyhow::Result;
+use engine::EngineStrategy;
+use evaluator::{EvaluatorStrategy, Weights};
+use heuristics::{GreedyStrategy, RandomStrategy, VarietyStrategy};
+use ismcts::IsmctsStrategy;
+use levels::Level;
+use pimc::PimcStrategy;
+use player::Strategy;
+use script::ScriptStrategy;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tablebase::Tablebase;
+
+/// Names accepted by `by_name`.
+pub const NAMES: [&str; 9] = [
+    "random",
+    "greedy",
+    "variety",
+    "evaluator",
+    "pimc",
+    "ismcts",
+    "beginner",
+    "intermediate",
+    "expert",
+];
+
+/// Builds a strategy from its name, seeded for the ones that need it.
+/// `engine:<program>` runs an external engine, `script:<file>` a Rhai
+/// script, `evaluator:<file>` the evaluator with trained weights and
+/// `pimc:<file>` the PIMC bot looking endgames up in a tablebase.
+pub fn by_name(name: &str, seed: u64) -> Option<Box<dyn Strategy>> {
+    if let Some(command) = name.strip_prefix("engine:") {
+        return Some(Box::new(EngineStrategy::new(command)));
+    }
+    if let Some(path) = name.strip_prefix("script:") {
+        return Some(Box::new(ScriptStrategy::new(path)));
+    }
+    if let Some(path) = name.strip_prefix("evaluator:") {
+        let weights = Weights::load(path).ok()?;
+        return Some(Box::new(EvaluatorStrategy::new(weights)));
+    }
+    if let Some(path) = name.strip_prefix("pimc:") {
+        let mut strategy = PimcStrategy::new(seed);
+        strategy.tablebase = Some(Arc::new(Tablebase::load(path).ok()?));
+        return Some(Box::new(strategy));
+    }
+
+    if let Some(level) = Level::parse(name) {
+        return Some(level.strategy(seed, None));
+    }
+
+    match name {
+        "random" => Some(Box::new(RandomStrategy::new(seed))),
+        "greedy" => Some(Box::new(GreedyStrategy)),
+        "variety" => Some(Box::new(VarietyStrategy)),
+        "evaluator" => Some(Box::new(EvaluatorStrategy::new(Weights::default()))),
+        "pimc" => Some(Box::new(PimcStrategy::new(seed))),
+        "ismcts" => Some(Box::new(IsmctsStrategy::new(seed))),
+        _ => None,
+    }
+}
+
+/// The files bots are read from, `evaluator:<file>` weights and
+/// `pimc:<file>` tablebases, loaded once for a lineup and shared by every
+/// bot made from them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BotFiles {
+    weights: HashMap<String, Weights>,
+    tablebases: HashMap<String, Arc<Tablebase>>,
+}
+
+impl BotFiles {
+    /// Loads the files the given bot names read.
+    pub fn load(names: &[String]) -> Result<BotFiles> {
+        let mut files = BotFiles::default();
+
+        for name in names {
+            if let Some(path) = name.strip_prefix("evaluator:") {
+                files.weights.insert(path.to_string(), Weights::load(path)?);
+            }
+            if let Some(path) = name.strip_prefix("pimc:") {
+                let tablebase = Arc::new(Tablebase::load(path)?);
+                files.tablebases.insert(path.to_string(), tablebase);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Builds a strategy as `by_name` does, from the files already loaded.
+    pub fn by_name(&self, name: &str, seed: u64) -> Option<Box<dyn Strategy>> {
+        let weights = name
+            .strip_prefix("evaluator:")
+            .and_then(|path| self.weights.get(path));
+        if let Some(weights) = weights {
+            return Some(Box::new(EvaluatorStrategy::new(*weights)));
+        }
+        let tablebase = name
+            .strip_prefix("pimc:")
+            .and_then(|path| self.tablebases.get(path));
+        if let Some(tablebase) = tablebase {
+            let mut strategy = PimcStrategy::new(seed);
+            strategy.tablebase = Some(tablebase.clone());
+            return Some(Box::new(strategy));
+        }
+
+        by_name(name, seed)
+    }
+}
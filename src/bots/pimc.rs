@@ -0,0 +1,151 @@
+use crate::bots::player::{Bot, Strategy};
+use crate::bots::playout::{random_playout, value};
+use crate::bots::solver;
+use crate::bots::tablebase::Tablebase;
+use crate::bots::view::PlayerView;
+use crate::concurrent::RemotePlayer;
+use crate::game::{Game, Move};
+use crate::rules::Rules;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Perfect information Monte Carlo: deals the unseen tiles out at random a
+/// number of times, plays every legal move out in each deal and keeps the
+/// move with the best total. Late in block games the deals are solved
+/// exactly instead.
+pub struct PimcStrategy {
+    rng: StdRng,
+    /// Deals sampled per decision.
+    pub samples: usize,
+    /// Random playouts of each move in each deal.
+    pub playouts: usize,
+    /// Tiles left in all hands at or below which a deal is solved rather
+    /// than played out, where the rules allow it.
+    pub solve_below: usize,
+    /// Stops sampling early once this much time has gone by.
+    pub time: Option<Duration>,
+    /// Endgames looked up rather than solved.
+    pub tablebase: Option<Arc<Tablebase>>,
+}
+
+impl PimcStrategy {
+    pub fn new(seed: u64) -> PimcStrategy {
+        PimcStrategy {
+            rng: StdRng::seed_from_u64(seed),
+            samples: 50,
+            playouts: 2,
+            solve_below: 10,
+            time: None,
+            tablebase: None,
+        }
+    }
+
+    /// Every legal move with its average value over the sampled deals.
+    pub fn evaluate(&mut self, view: &PlayerView) -> Vec<(Move, f64)> {
+        let moves = view.legal_moves();
+        let started = Instant::now();
+        let mut totals = vec![0i64; moves.len()];
+        let mut runs = 0;
+
+        for _ in 0..self.samples {
+            if let Some(time) = self.time {
+                if started.elapsed() >= time {
+                    break;
+                }
+            }
+
+            let mut world = view.sample(&mut self.rng);
+            world.next = view.seat as i32;
+            runs += self.playouts;
+
+            if self.solves(&world) {
+                for (total, move_) in totals.iter_mut().zip(moves.iter()) {
+                    let mut game = world.clone();
+                    if game.play(move_).is_ok() {
+                        *total += self.playouts as i64 * self.solved(&game, view.seat) as i64;
+                    }
+                }
+                continue;
+            }
+
+            for (total, move_) in totals.iter_mut().zip(moves.iter()) {
+                for _ in 0..self.playouts {
+                    let mut game = world.clone();
+                    if game.play(move_).is_err() {
+                        continue;
+                    }
+
+                    let outcome = random_playout(&mut game, &mut self.rng);
+                    *total += value(&game, outcome, view.seat) as i64;
+                }
+            }
+        }
+
+        moves
+            .into_iter()
+            .zip(totals)
+            .map(|(move_, total)| (move_, total as f64 / runs.max(1) as f64))
+            .collect()
+    }
+
+    fn solves(&self, world: &Game) -> bool {
+        let tiles = world.players.iter().map(|hand| hand.len()).sum::<usize>();
+        tiles <= self.solve_below && solver::solvable(&world.rules)
+    }
+
+    /// Value of a deal for the seat, looked up or solved exactly.
+    fn solved(&self, game: &Game, seat: usize) -> i32 {
+        let tablebase = self.tablebase.as_ref();
+        if let Some(value) = tablebase.and_then(|tablebase| tablebase.probe_for(game, seat)) {
+            return value;
+        }
+
+        match game.outcome() {
+            Some(outcome) => value(game, outcome, seat),
+            None => solver::evaluate(game, seat).unwrap_or(0),
+        }
+    }
+}
+
+impl Strategy for PimcStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        let moves = view.legal_moves();
+        if moves.len() == 1 {
+            return moves[0];
+        }
+
+        self.evaluate(view)
+            .into_iter()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(move_, _)| move_)
+            .unwrap_or(moves[0])
+    }
+}
+
+pub fn pimc_bot(rules: Rules, n_players: i32, seed: u64) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, PimcStrategy::new(seed)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::heuristics::RandomStrategy;
+    use crate::bots::playout::head_to_head;
+
+    #[test]
+    fn test_pimc_beats_random() {
+        let hands = 60;
+        let wins = head_to_head(&Rules::block(), hands, 3, |hand| {
+            [
+                Box::new(PimcStrategy::new(hand)),
+                Box::new(RandomStrategy::new(hand)),
+            ]
+        });
+
+        // At least three hands in four, where random play would win half.
+        assert!(wins[0] * 4 >= hands as usize * 3, "won {:?}", wins);
+        assert!(wins[0] >= 2 * wins[1]);
+    }
+}
@@ -0,0 +1,137 @@
+use crate::bots::view::PlayerView;
+use crate::concurrent::{Message, RemotePlayer, TimeLeft};
+use crate::game::Move;
+use crate::rules::{Goal, Rules};
+use async_trait::async_trait;
+use std::sync::{Arc, Mutex};
+
+/// Picks a move for the seat on turn from what it can see.
+pub trait Strategy: Send + Sync {
+    /// Called once a hand is dealt, before anything is played.
+    fn deal(&mut self, _view: &PlayerView) {}
+
+    /// Called when the seat is on turn at a timed table, before `choose`.
+    fn turn(&mut self, _time_left: TimeLeft) {}
+
+    fn choose(&mut self, view: &PlayerView) -> Move;
+}
+
+impl Strategy for Box<dyn Strategy> {
+    fn deal(&mut self, view: &PlayerView) {
+        self.as_mut().deal(view)
+    }
+
+    fn turn(&mut self, time_left: TimeLeft) {
+        self.as_mut().turn(time_left)
+    }
+
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        self.as_mut().choose(view)
+    }
+}
+
+/// An in-process seat: follows the table through its messages and answers
+/// with whatever its strategy picks. The strategy is run off the async
+/// tasks, as searching a move may take seconds, and is shared with the
+/// search under way so that it outlives a turn given up before it ends.
+pub struct Bot<S: Strategy> {
+    rules: Rules,
+    n_players: i32,
+    number: usize,
+    /// Hands dealt so far, used to tell which opening double applies.
+    hands: usize,
+    view: Option<PlayerView>,
+    strategy: Arc<Mutex<S>>,
+}
+
+impl<S: Strategy> Bot<S> {
+    pub fn new(rules: Rules, n_players: i32, strategy: S) -> Bot<S> {
+        Bot {
+            rules,
+            n_players,
+            number: 0,
+            hands: 0,
+            view: None,
+            strategy: Arc::new(Mutex::new(strategy)),
+        }
+    }
+
+    pub fn view(&self) -> Option<&PlayerView> {
+        self.view.as_ref()
+    }
+}
+
+#[async_trait]
+impl<S: Strategy + 'static> RemotePlayer for Bot<S> {
+    async fn send_message<'a>(&mut self, message: Message<'a>) {
+        match message {
+            Message::Init((pieces, number)) => {
+                // Every board of a duplicate match is dealt once per seat.
+                let board = match self.rules.goal {
+                    Goal::Duplicate(_) => self.hands / self.n_players as usize,
+                    _ => self.hands,
+                };
+
+                let view = PlayerView::new(
+                    self.rules.clone(),
+                    self.n_players,
+                    board,
+                    number,
+                    pieces.clone(),
+                );
+                // Engines are started on the first deal, which takes a while.
+                let strategy = self.strategy.clone();
+                let dealt = view.clone();
+                tokio::task::spawn_blocking(move || strategy.lock().unwrap().deal(&dealt))
+                    .await
+                    .unwrap();
+
+                self.number = number;
+                self.view = Some(view);
+                self.hands += 1;
+            }
+
+            Message::Update((player, update, _)) => {
+                if let Some(view) = &mut self.view {
+                    let _ = view.apply(player, &update);
+                }
+            }
+
+            Message::Drawn(piece) => {
+                if let Some(view) = &mut self.view {
+                    view.drawn(piece);
+                }
+            }
+
+            Message::YourTurn(Some(time_left)) => {
+                self.strategy.lock().unwrap().turn(time_left);
+            }
+
+            Message::YourTurn(None)
+            | Message::HandOver(_)
+            | Message::Misdeal(_)
+            | Message::Hints(_)
+            | Message::Blunder(_)
+            | Message::Forfeit(_) => {}
+        }
+    }
+
+    async fn read_move(&mut self) -> Move {
+        let Some(view) = self.view.clone() else {
+            return Move::Pass(self.number);
+        };
+
+        let strategy = self.strategy.clone();
+        tokio::task::spawn_blocking(move || strategy.lock().unwrap().choose(&view))
+            .await
+            .unwrap()
+    }
+
+    fn number(&self) -> usize {
+        self.number
+    }
+
+    fn set_number(&mut self, number: usize) {
+        self.number = number;
+    }
+}
@@ -0,0 +1,157 @@
+use crate::bots::player::Strategy;
+use crate::bots::view::PlayerView;
+use crate::game::{Game, Outcome, Update};
+use crate::log::{Event, GameLog};
+use crate::rules::Rules;
+use crate::scoring::{hand_points, Match};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// Plays the given hand of a match to the end between strategies, without going through
+/// the network table. Each strategy only sees what its seat would be sent;
+/// one that asks for an illegal move plays the first legal one instead.
+/// The moves made are added to `log`.
+pub fn play_hand(
+    game: &mut Game,
+    hand: usize,
+    strategies: &mut [Box<dyn Strategy>],
+    log: &mut GameLog,
+) -> Outcome {
+    let n_players = game.n_players() as usize;
+
+    let mut views = (0..n_players)
+        .map(|seat| {
+            let tiles = game.players[seat]
+                .iter()
+                .filter(|piece| !game.opening_draws.contains(&(seat, **piece)))
+                .copied()
+                .collect();
+            PlayerView::new(game.rules.clone(), n_players as i32, hand, seat, tiles)
+        })
+        .collect::<Vec<_>>();
+    for (view, strategy) in views.iter_mut().zip(strategies.iter_mut()) {
+        view.game.next = game.next;
+        strategy.deal(view);
+    }
+
+    for (seat, piece) in game.opening_draws.clone() {
+        views[seat].drawn(piece);
+        for view in views.iter_mut() {
+            let _ = view.apply(seat, &Update::Draw);
+        }
+    }
+
+    loop {
+        if let Some(outcome) = game.outcome() {
+            return outcome;
+        }
+
+        let turn = game.next as usize;
+        let mut move_ = strategies[turn].choose(&views[turn]);
+
+        let update = match game.play(&move_) {
+            Ok(update) => update,
+            Err(_) => {
+                move_ = game.legal_moves(turn)[0];
+                game.play(&move_).unwrap()
+            }
+        };
+        log.push(Event::Move(move_));
+
+        if let (Update::Draw, Some(piece)) = (update, game.players[turn].last()) {
+            views[turn].drawn(*piece);
+        }
+
+        for view in views.iter_mut() {
+            let _ = view.apply(turn, &update);
+        }
+    }
+}
+
+/// Plays a whole match between strategies, one per player, dealing from the
+/// given source of randomness.
+pub fn play_match(rules: Rules, strategies: &mut [Box<dyn Strategy>], rng: &mut impl Rng) -> Match {
+    let n_players = strategies.len();
+    let mut match_ = Match::new(rules, n_players as i32);
+    let mut rotation = 0;
+
+    while !match_.is_over() {
+        // Strategies are kept in seat order.
+        strategies.rotate_right((match_.rotation() + n_players - rotation) % n_players);
+        rotation = match_.rotation();
+
+        let mut game = match_.deal_with(rng);
+        let outcome = play_hand(&mut game, match_.board(), strategies, &mut match_.log);
+        match_.record(&game, outcome);
+    }
+
+    strategies.rotate_left(rotation);
+    match_
+}
+
+/// Plays `hands` two-player hands dealt from `seed` between the strategies
+/// `make` builds for each hand, which swap seats every other hand, and
+/// counts the hands each of them won.
+pub fn head_to_head(
+    rules: &Rules,
+    hands: u64,
+    seed: u64,
+    make: impl Fn(u64) -> [Box<dyn Strategy>; 2],
+) -> [usize; 2] {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut wins = [0; 2];
+
+    for hand in 0..hands {
+        let swapped = hand % 2 == 1;
+        let mut strategies = Vec::from(make(hand));
+        if swapped {
+            strategies.reverse();
+        }
+
+        let mut game = Game::deal_with(rules.clone(), 2, 0, &mut rng);
+        let outcome = play_hand(&mut game, 0, &mut strategies, &mut GameLog::new());
+
+        let winner = match outcome {
+            Outcome::Won(seat) => Some(seat),
+            Outcome::Blocked => game.blocked_winner(),
+        };
+        if let Some(seat) = winner {
+            wins[(seat == 1) as usize ^ swapped as usize] += 1;
+        }
+    }
+
+    wins
+}
+
+/// Finishes the hand with random legal moves.
+pub fn random_playout(game: &mut Game, rng: &mut impl Rng) -> Outcome {
+    loop {
+        if let Some(outcome) = game.outcome() {
+            return outcome;
+        }
+
+        let moves = game.legal_moves(game.next as usize);
+        let move_ = moves.choose(rng).unwrap();
+        let _ = game.play(move_);
+    }
+}
+
+/// How good a finished hand is for the given seat: the points its side
+/// scored less the best opposing side's, or the other way round when the
+/// lowest score wins.
+pub fn value(game: &Game, outcome: Outcome, seat: usize) -> i32 {
+    let rules = &game.rules;
+    let n_players = game.n_players() as usize;
+    let points = hand_points(rules, game, outcome);
+
+    let opponents = (0..n_players)
+        .filter(|other| !rules.same_side(n_players, seat, *other))
+        .map(|other| points[other]);
+
+    if rules.lowest_wins {
+        opponents.min().unwrap_or(0) - points[seat]
+    } else {
+        points[seat] - opponents.max().unwrap_or(0)
+    }
+}
@@ -0,0 +1,236 @@
+use crate::bots::heuristics::GreedyStrategy;
+use crate::bots::player::{Bot, Strategy};
+use crate::bots::view::PlayerView;
+use crate::concurrent::RemotePlayer;
+use crate::game::{Domino, End, Move};
+use crate::rules::Rules;
+use anyhow::{anyhow, Result};
+use rhai::{Array, Dynamic, Engine, Map, Scope, AST, INT};
+use std::path::{Path, PathBuf};
+
+/// Operations a script may run per move before it is stopped.
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// A bot whose moves are picked by a Rhai script. The script defines
+///
+/// ```text
+/// fn choose(view, moves) { ... }
+/// ```
+///
+/// and returns the index of the move it wants. `view` is a map with the
+/// seat, its `hand`, the `board`, the numbers on the open `ends`, the
+/// `hand_sizes` of every seat, the tiles left in the `boneyard` and the
+/// `unseen` tiles; tiles are `[a, b]` arrays. Each move is a map with its
+/// `kind` (`left`, `right`, `branch`, `draw` or `pass`), the `pips` it gets
+/// rid of and, for placements, the `tile`, the `end` it goes on and the
+/// `branch`.
+///
+/// The script is read again whenever the file changes; while the file does
+/// not read or compile, the script last compiled keeps playing. Scripts have no
+/// access to the file system and are stopped after `MAX_OPERATIONS`; a
+/// script that fails for any reason plays the greedy move instead.
+pub struct ScriptStrategy {
+    path: PathBuf,
+    engine: Engine,
+    /// The source last read, whether it compiled or not.
+    source: Option<String>,
+    /// The script last compiled.
+    script: Option<AST>,
+}
+
+impl ScriptStrategy {
+    pub fn new(path: impl AsRef<Path>) -> ScriptStrategy {
+        let mut engine = Engine::new();
+        engine
+            .set_max_operations(MAX_OPERATIONS)
+            .set_max_call_levels(32)
+            .set_max_expr_depths(64, 32)
+            .set_max_string_size(10_000)
+            .set_max_array_size(10_000)
+            .set_max_map_size(1_000)
+            .disable_symbol("eval");
+
+        ScriptStrategy {
+            path: path.as_ref().to_path_buf(),
+            engine,
+            source: None,
+            script: None,
+        }
+    }
+
+    /// The script to play, compiled again if the file changed since the
+    /// last move.
+    fn load(&mut self) -> Result<AST> {
+        if let Err(error) = self.reload() {
+            eprintln!("{}: {}", self.path.display(), error);
+        }
+
+        self.script
+            .clone()
+            .ok_or_else(|| anyhow!("No script loaded".to_string()))
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let source = std::fs::read_to_string(&self.path)?;
+
+        if self.source.as_ref() != Some(&source) {
+            let ast = self.engine.compile(&source);
+            self.source = Some(source);
+            self.script = Some(ast?);
+        }
+
+        Ok(())
+    }
+
+    fn run(&mut self, view: &PlayerView, moves: &[Move]) -> Result<Move> {
+        let view_map = view_map(view);
+        let move_maps = moves
+            .iter()
+            .map(|move_| Dynamic::from_map(move_map(view, move_)))
+            .collect::<Array>();
+
+        let ast = self.load()?;
+        let choice =
+            self.engine
+                .call_fn::<INT>(&mut Scope::new(), &ast, "choose", (view_map, move_maps))?;
+
+        usize::try_from(choice)
+            .ok()
+            .and_then(|choice| moves.get(choice).copied())
+            .ok_or_else(|| anyhow!("The script chose move {} of {}", choice, moves.len()))
+    }
+}
+
+impl Strategy for ScriptStrategy {
+    fn choose(&mut self, view: &PlayerView) -> Move {
+        let moves = view.legal_moves();
+
+        match self.run(view, &moves) {
+            Ok(move_) => move_,
+            Err(error) => {
+                eprintln!("{}: {}", self.path.display(), error);
+                GreedyStrategy.choose(view)
+            }
+        }
+    }
+}
+
+fn tile(domino: &Domino) -> Dynamic {
+    Dynamic::from_array(vec![(domino.0 as INT).into(), (domino.1 as INT).into()])
+}
+
+fn tiles(dominoes: &[Domino]) -> Dynamic {
+    Dynamic::from_array(dominoes.iter().map(tile).collect())
+}
+
+fn numbers(numbers: impl Iterator<Item = usize>) -> Dynamic {
+    Dynamic::from_array(numbers.map(|number| (number as INT).into()).collect())
+}
+
+fn view_map(view: &PlayerView) -> Map {
+    let mut map = Map::new();
+    let n_players = view.game.n_players() as usize;
+
+    map.insert("seat".into(), (view.seat as INT).into());
+    map.insert("hand".into(), tiles(view.hand()));
+    map.insert("board".into(), tiles(&view.game.board));
+    map.insert(
+        "ends".into(),
+        Dynamic::from_array(
+            view.game
+                .open_ends()
+                .iter()
+                .map(|(_, number)| (*number as INT).into())
+                .collect(),
+        ),
+    );
+    map.insert(
+        "hand_sizes".into(),
+        numbers(view.hand_sizes().into_iter().take(n_players)),
+    );
+    map.insert("boneyard".into(), (view.game.boneyard.len() as INT).into());
+    map.insert("unseen".into(), tiles(&view.unseen()));
+
+    map
+}
+
+fn move_map(view: &PlayerView, move_: &Move) -> Map {
+    let mut map = Map::new();
+
+    let (kind, end) = match *move_ {
+        Move::Left(..) => ("left", Some(End::Left)),
+        Move::Right(..) => ("right", Some(End::Right)),
+        Move::Branch(_, branch, _) => ("branch", Some(End::Branch(branch))),
+        Move::Draw(_) => ("draw", None),
+        Move::Pass(_) => ("pass", None),
+    };
+    map.insert("kind".into(), kind.into());
+
+    let piece = view.piece(move_);
+    if let Some(piece) = piece {
+        map.insert("tile".into(), tile(&piece));
+    }
+    let pips = piece.map(|piece| piece.pips()).unwrap_or(0);
+    map.insert("pips".into(), (pips as INT).into());
+
+    let open = view.game.open_ends();
+    if let Some((_, number)) = open.iter().find(|(open, _)| Some(*open) == end) {
+        map.insert("end".into(), (*number as INT).into());
+    }
+    if let Move::Branch(_, branch, _) = *move_ {
+        map.insert("branch".into(), (branch as INT).into());
+    }
+
+    map
+}
+
+/// Seats a script at a network table.
+pub fn script_bot(rules: Rules, n_players: i32, path: impl AsRef<Path>) -> Box<dyn RemotePlayer> {
+    Box::new(Bot::new(rules, n_players, ScriptStrategy::new(path)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_scripts_pick_reload_and_fall_back() {
+        let name = format!("rustominoes-test-bot-{}.rhai", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        let mut rng = StdRng::seed_from_u64(2);
+        let game = Game::deal_with(Rules::block(), 2, 0, &mut rng);
+        let view = PlayerView::new(Rules::block(), 2, 0, 0, game.players[0].clone());
+        let moves = view.legal_moves();
+        let greedy = GreedyStrategy.choose(&view);
+        let mut script = ScriptStrategy::new(&path);
+
+        std::fs::write(&path, "fn choose(view, moves) { moves.len() - 1 }").unwrap();
+        assert_eq!(moves[moves.len() - 1], script.choose(&view));
+
+        std::fs::write(
+            &path,
+            "fn choose(view, moves) { if view.hand.len() == 14 { 1 } else { 0 } }",
+        )
+        .unwrap();
+        assert_eq!(moves[1], script.choose(&view));
+
+        // A file that does not compile or is gone leaves the last script.
+        std::fs::write(&path, "fn choose(view, moves) {").unwrap();
+        assert_eq!(moves[1], script.choose(&view));
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(moves[1], script.choose(&view));
+
+        for broken in [
+            "fn choose(view, moves) { loop {} }",
+            "fn choose(view, moves) { 100 }",
+            "fn choose(view, moves) { eval(\"1\") }",
+        ] {
+            std::fs::write(&path, broken).unwrap();
+            assert_eq!(greedy, script.choose(&view));
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+}
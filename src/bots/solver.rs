@@ -0,0 +1,398 @@
+use crate::bots::playout::value;
+use crate::game::{Domino, Game, Move, Outcome};
+use crate::rules::Rules;
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+
+/// A position solved with every hand in view.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    /// Value of the position for the side on turn under best play, as
+    /// `playout::value` counts it.
+    pub value: i32,
+    /// Every legal move with the value it leads to.
+    pub moves: Vec<(Move, i32)>,
+    pub best: Move,
+    /// The moves of best play to the end of the hand and how it ends.
+    pub line: Vec<Move>,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+/// Everything about a block position that matters for the rest of the hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Key {
+    hands: [u128; 4],
+    ends: (i32, i32),
+    next: i32,
+    passes: i32,
+    blocker: Option<usize>,
+}
+
+/// Whether the solver can handle hands played under these rules: a single
+/// line, no drawing and nothing scored before the hand ends.
+pub fn solvable(rules: &Rules) -> bool {
+    !rules.draw && !rules.fives && !rules.headers && !rules.spinner && rules.max_pip <= 12
+}
+
+/// Solves a block position exactly with alpha-beta search over a
+/// transposition table. With more than two sides every seat plays for itself
+/// instead (max-n), which cannot be pruned. A full deal for four partners
+/// takes seconds; two players holding fourteen tiles each, or three or four
+/// players on their own, take much longer.
+pub fn solve(game: &Game) -> Result<Solution> {
+    check(game)?;
+    if game.outcome().is_some() {
+        return Err(anyhow!("The hand is over".to_string()));
+    }
+
+    let seat = game.next as usize;
+    let mut solver = Solver::new(game, seat);
+
+    let moves = game
+        .legal_moves(seat)
+        .into_iter()
+        .map(|move_| {
+            let mut next = game.clone();
+            let _ = next.play(&move_);
+            (move_, solver.value(&next))
+        })
+        .collect::<Vec<_>>();
+
+    let (best, value) = *moves.iter().max_by_key(|(_, value)| *value).unwrap();
+
+    let mut line = vec![best];
+    let mut position = game.clone();
+    let _ = position.play(&best);
+    while position.outcome().is_none() {
+        let move_ = solver.best(&position);
+        let _ = position.play(&move_);
+        line.push(move_);
+    }
+
+    Ok(Solution {
+        value,
+        moves,
+        best,
+        line,
+        outcome: position.outcome().unwrap(),
+    })
+}
+
+/// Value of a position for the given seat under best play, as `solve`
+/// plays it.
+pub fn evaluate(game: &Game, seat: usize) -> Result<i32> {
+    check(game)?;
+
+    Ok(Solver::new(game, seat).value(game))
+}
+
+fn check(game: &Game) -> Result<()> {
+    if !solvable(&game.rules) || !game.branches.is_empty() {
+        return Err(anyhow!("Only block games can be solved".to_string()));
+    }
+    Ok(())
+}
+
+struct Solver {
+    seat: usize,
+    /// More than two sides, each seat playing for itself.
+    max_n: bool,
+    table: HashMap<Key, (i32, Bound)>,
+    values: HashMap<Key, [i32; 4]>,
+}
+
+impl Solver {
+    fn new(game: &Game, seat: usize) -> Solver {
+        let n_players = game.n_players() as usize;
+        let sides = (0..n_players)
+            .filter(|seat| (0..*seat).all(|other| !game.rules.same_side(n_players, *seat, other)))
+            .count();
+
+        Solver {
+            seat,
+            max_n: sides > 2,
+            table: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    fn value(&mut self, game: &Game) -> i32 {
+        if self.max_n {
+            self.values(game)[self.seat]
+        } else {
+            self.search(game, i32::MIN, i32::MAX)
+        }
+    }
+
+    fn maximizing(&self, game: &Game) -> bool {
+        let n_players = game.n_players() as usize;
+        game.rules
+            .same_side(n_players, self.seat, game.next as usize)
+    }
+
+    fn search(&mut self, game: &Game, mut alpha: i32, mut beta: i32) -> i32 {
+        if let Some(outcome) = game.outcome() {
+            return value(game, outcome, self.seat);
+        }
+
+        let key = key(game);
+        if let Some((value, bound)) = self.table.get(&key).copied() {
+            match bound {
+                Bound::Exact => return value,
+                Bound::Lower => alpha = alpha.max(value),
+                Bound::Upper => beta = beta.min(value),
+            }
+            if alpha >= beta {
+                return value;
+            }
+        }
+
+        let (alpha_in, beta_in) = (alpha, beta);
+        let maximizing = self.maximizing(game);
+        let mut best = if maximizing { i32::MIN } else { i32::MAX };
+
+        for move_ in game.legal_moves(game.next as usize) {
+            let mut next = game.clone();
+            let _ = next.play(&move_);
+            let value = self.search(&next, alpha, beta);
+
+            if maximizing {
+                best = best.max(value);
+                alpha = alpha.max(value);
+            } else {
+                best = best.min(value);
+                beta = beta.min(value);
+            }
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best <= alpha_in {
+            Bound::Upper
+        } else if best >= beta_in {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(key, (best, bound));
+
+        best
+    }
+
+    /// Values of a position for every seat when each plays for itself: the
+    /// seat on turn makes the move worth most to it.
+    fn values(&mut self, game: &Game) -> [i32; 4] {
+        if let Some(outcome) = game.outcome() {
+            let n_players = game.n_players() as usize;
+            return std::array::from_fn(|seat| match seat < n_players {
+                true => value(game, outcome, seat),
+                false => 0,
+            });
+        }
+
+        let key = key(game);
+        if let Some(values) = self.values.get(&key) {
+            return *values;
+        }
+
+        let seat = game.next as usize;
+        let best = game
+            .legal_moves(seat)
+            .into_iter()
+            .map(|move_| {
+                let mut next = game.clone();
+                let _ = next.play(&move_);
+                self.values(&next)
+            })
+            .max_by_key(|values| values[seat])
+            .unwrap();
+        self.values.insert(key, best);
+
+        best
+    }
+
+    /// The move the seat on turn makes under best play.
+    fn best(&mut self, game: &Game) -> Move {
+        if self.max_n {
+            let seat = game.next as usize;
+            let scored = game
+                .legal_moves(seat)
+                .into_iter()
+                .map(|move_| {
+                    let mut next = game.clone();
+                    let _ = next.play(&move_);
+                    (move_, self.values(&next)[seat])
+                })
+                .collect::<Vec<_>>();
+            return scored.iter().max_by_key(|(_, value)| *value).unwrap().0;
+        }
+
+        let maximizing = self.maximizing(game);
+
+        let scored = game
+            .legal_moves(game.next as usize)
+            .into_iter()
+            .map(|move_| {
+                let mut next = game.clone();
+                let _ = next.play(&move_);
+                (move_, self.search(&next, i32::MIN, i32::MAX))
+            })
+            .collect::<Vec<_>>();
+
+        let pick = if maximizing {
+            scored.iter().max_by_key(|(_, value)| *value)
+        } else {
+            scored.iter().min_by_key(|(_, value)| *value)
+        };
+        pick.unwrap().0
+    }
+}
+
+pub(crate) fn index(domino: &Domino) -> u32 {
+    let (high, low) = (domino.0.max(domino.1), domino.0.min(domino.1));
+    (high * (high + 1) / 2 + low) as u32
+}
+
+fn key(game: &Game) -> Key {
+    let mut hands = [0u128; 4];
+    for (mask, hand) in hands.iter_mut().zip(game.players.iter()) {
+        for domino in hand {
+            *mask |= 1 << index(domino);
+        }
+    }
+
+    let ends = match (game.board.first(), game.board.last()) {
+        (Some(left), Some(right)) => (left.0, right.1),
+        _ => (-1, -1),
+    };
+
+    Key {
+        hands,
+        ends,
+        next: game.next,
+        passes: game.passes(),
+        blocker: game.last.map(|placement| placement.player),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(hands: [Vec<Domino>; 4], board: Vec<Domino>, n_players: i32) -> Game {
+        let mut game = Game::deal(Rules::block(), n_players, 0);
+        game.players = hands;
+        game.board = board;
+        game.boneyard.clear();
+        game.next = 0;
+        game
+    }
+
+    #[test]
+    fn test_solve_finds_the_blocking_play() {
+        // Opening up the 2 lets seat 1 through; keeping 6s and 3s on the
+        // ends makes it pass.
+        let game = position(
+            [
+                vec![Domino(6, 3), Domino(6, 2), Domino(3, 1)],
+                vec![Domino(2, 5), Domino(5, 5)],
+                vec![],
+                vec![],
+            ],
+            vec![Domino(6, 6)],
+            2,
+        );
+
+        let solution = solve(&game).unwrap();
+
+        assert_eq!(Outcome::Won(0), solution.outcome);
+        assert!(solution.value > 0);
+        assert_eq!(Some(&solution.best), solution.line.first());
+        for (move_, value) in &solution.moves {
+            let piece = game.players[0][index_of(move_)];
+            assert_eq!(piece != Domino(6, 2), *value > 0);
+        }
+    }
+
+    fn index_of(move_: &Move) -> usize {
+        match *move_ {
+            Move::Left(_, pos) | Move::Right(_, pos) | Move::Branch(_, _, pos) => pos,
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn test_solve_rejects_draw_games() {
+        let game = Game::deal(Rules::sniff(), 2, 0);
+
+        assert!(solve(&game).is_err());
+    }
+
+    /// Solves the position both ways: with every seat for itself, and
+    /// with seat 0 against all the others together.
+    fn solve_on_their_own(game: &Game) -> (Solution, i32) {
+        let solution = solve(game).unwrap();
+
+        let mut paranoid = Solver::new(game, 0);
+        assert!(paranoid.max_n);
+        paranoid.max_n = false;
+
+        let mut position = game.clone();
+        for move_ in &solution.line {
+            position.play(move_).unwrap();
+        }
+        assert_eq!(Some(solution.outcome), position.outcome());
+
+        (solution, paranoid.value(game))
+    }
+
+    #[test]
+    fn test_solve_three_players() {
+        // Seat 2 would rather play 6-4 and let seat 0 out for 11 than 5-2,
+        // letting seat 1 out for 16, which shuts out seat 0
+        let game = position(
+            [
+                vec![Domino(1, 1), Domino(6, 0)],
+                vec![Domino(2, 1), Domino(4, 0)],
+                vec![Domino(5, 2), Domino(6, 4)],
+                vec![],
+            ],
+            vec![Domino(4, 1)],
+            3,
+        );
+
+        let (solution, paranoid) = solve_on_their_own(&game);
+
+        assert_eq!(Outcome::Won(0), solution.outcome);
+        assert_eq!(11, solution.value);
+        assert!(paranoid < 0);
+    }
+
+    #[test]
+    fn test_solve_four_players_without_partners() {
+        let game = position(
+            [
+                vec![Domino(5, 2), Domino(2, 2)],
+                vec![Domino(6, 5), Domino(6, 2)],
+                vec![Domino(6, 6), Domino(4, 3)],
+                vec![Domino(5, 4), Domino(6, 0)],
+            ],
+            vec![Domino(4, 2)],
+            4,
+        );
+
+        let (solution, paranoid) = solve_on_their_own(&game);
+
+        assert_eq!(Outcome::Won(0), solution.outcome);
+        assert_eq!(24, solution.value);
+        assert!(paranoid < 0);
+    }
+}
@@ -0,0 +1,504 @@
+use crate::bots::playout::value;
+use crate::bots::solver;
+use crate::game::{Domino, End, Game, Move, Outcome, Placement};
+use crate::rules::{parse_tranque, tranque_name, Matching, Rules, Tranque, Variant};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+/// Version of the tablebase file format.
+pub const VERSION: u32 = 2;
+
+/// Most tiles a tablebase is built for. Each tile more takes about eight
+/// times the positions: five tiles make about three million.
+pub const MAX_TILES: usize = 6;
+
+/// Tiles in a double-six set, the only one tablebases are built for.
+const SET: usize = 28;
+
+/// Exact values of two-player block endgames with few tiles left in hand,
+/// worked out backwards from the end of the hand.
+///
+/// Positions are stored from the side of the seat on turn: the tiles it
+/// holds, the tiles its opponent holds, the two open ends in either order
+/// and whether the opponent has just passed. Every tile is dealt in a
+/// two-player block hand, so the board holds the rest of the set and only
+/// end pairs its tiles can leave are generated.
+///
+/// The file is a header line with the format version, the scoring rules
+/// the values were worked out under and the number of positions, followed
+/// by one record per position, sorted by key: the key as 8 bytes and the value as 2, both
+/// little-endian.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tablebase {
+    /// Tiles left in both hands together, at most.
+    pub tiles: usize,
+    pub tranque: Tranque,
+    pub lowest_wins: bool,
+    records: Vec<(u64, i16)>,
+}
+
+/// Whether tablebases can be built for hands played under these rules.
+pub fn supports(rules: &Rules) -> bool {
+    rules.variant == Variant::Block
+        && rules.max_pip == 6
+        && rules.matching == Matching::Equal
+        && solver::solvable(rules)
+}
+
+impl Tablebase {
+    /// Works out every position with up to `tiles` tiles left in hand,
+    /// starting from the ones a tile away from the end of the hand.
+    pub fn generate(rules: &Rules, tiles: usize) -> Result<Tablebase> {
+        if !supports(rules) {
+            return Err(anyhow!(
+                "Tablebases are only built for double-six block games".to_string()
+            ));
+        }
+        if tiles > MAX_TILES {
+            return Err(anyhow!(
+                "Tablebases are built for at most {} tiles",
+                MAX_TILES
+            ));
+        }
+
+        let mut generator = Generator::new(rules);
+        for count in 2..=tiles {
+            generator.layer(count);
+        }
+
+        let mut records = generator.values.into_iter().collect::<Vec<_>>();
+        records.sort_unstable();
+
+        Ok(Tablebase {
+            tiles,
+            tranque: rules.tranque,
+            lowest_wins: rules.lowest_wins,
+            records,
+        })
+    }
+
+    /// Number of positions held.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Whether the values hold for hands played under these rules.
+    pub fn covers(&self, rules: &Rules) -> bool {
+        supports(rules) && rules.tranque == self.tranque && rules.lowest_wins == self.lowest_wins
+    }
+
+    /// Value of the position for the seat on turn under best play, as
+    /// `playout::value` counts it, if it is in the tablebase.
+    pub fn probe(&self, game: &Game) -> Option<i32> {
+        let key = self.key(game)?;
+        let found = self
+            .records
+            .binary_search_by_key(&key, |(key, _)| *key)
+            .ok()?;
+
+        Some(self.records[found].1 as i32)
+    }
+
+    /// Value of the position for the given seat under best play.
+    pub fn probe_for(&self, game: &Game, seat: usize) -> Option<i32> {
+        if let Some(outcome) = game.outcome() {
+            return Some(value(game, outcome, seat));
+        }
+
+        let value = self.probe(game)?;
+        match seat == game.next as usize {
+            true => Some(value),
+            false => Some(-value),
+        }
+    }
+
+    /// Every legal move of the seat on turn with the value it leads to, if
+    /// the tablebase holds them all.
+    pub fn moves(&self, game: &Game) -> Option<Vec<(Move, i32)>> {
+        let seat = game.next as usize;
+
+        game.legal_moves(seat)
+            .into_iter()
+            .map(|move_| {
+                let mut next = game.clone();
+                next.play(&move_).ok()?;
+                Some((move_, self.probe_for(&next, seat)?))
+            })
+            .collect()
+    }
+
+    /// The move of best play, if the tablebase holds the position.
+    pub fn best(&self, game: &Game) -> Option<Move> {
+        self.moves(game)?
+            .into_iter()
+            .max_by_key(|(_, value)| *value)
+            .map(|(move_, _)| move_)
+    }
+
+    fn key(&self, game: &Game) -> Option<u64> {
+        let seat = game.next as usize;
+        let tiles = game.players.iter().map(|hand| hand.len()).sum::<usize>();
+
+        if game.n_players() != 2
+            || tiles > self.tiles
+            || !game.branches.is_empty()
+            || !game.boneyard.is_empty()
+            || !self.covers(&game.rules)
+            || game.outcome().is_some()
+        {
+            return None;
+        }
+
+        let (left, right) = match (game.board.first(), game.board.last()) {
+            (Some(left), Some(right)) => (left.0, right.1),
+            _ => return None,
+        };
+
+        Some(key(
+            mask(&game.players[seat])?,
+            mask(&game.players[1 - seat])?,
+            (left, right),
+            game.passes() > 0,
+        ))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+        writeln!(
+            file,
+            "tablebase {} tiles={} tranque={} lowest-wins={} positions={}",
+            VERSION,
+            self.tiles,
+            tranque_name(self.tranque),
+            if self.lowest_wins { "on" } else { "off" },
+            self.records.len()
+        )?;
+        for (key, value) in &self.records {
+            file.write_all(&key.to_le_bytes())?;
+            file.write_all(&value.to_le_bytes())?;
+        }
+
+        Ok(file.flush()?)
+    }
+
+    /// Reads a tablebase saved by `save`, checking that its records are
+    /// whole, in order and within the tiles it was built for.
+    pub fn load(path: impl AsRef<Path>) -> Result<Tablebase> {
+        let mut file = BufReader::new(std::fs::File::open(path)?);
+
+        let mut header = String::new();
+        file.read_line(&mut header)?;
+        let mut words = header.split_whitespace();
+        if words.next() != Some("tablebase") {
+            return Err(anyhow!("Not a tablebase".to_string()));
+        }
+        match words.next().map(|version| version.parse::<u32>()) {
+            Some(Ok(VERSION)) => {}
+            _ => return Err(anyhow!("Unsupported tablebase version".to_string())),
+        }
+
+        let mut tablebase = Tablebase {
+            tiles: 0,
+            tranque: Tranque::LowestTeam,
+            lowest_wins: false,
+            records: Vec::new(),
+        };
+        let mut positions = None;
+        for word in words {
+            match word.split_once('=') {
+                Some(("tiles", tiles)) => tablebase.tiles = tiles.parse()?,
+                Some(("tranque", name)) => tablebase.tranque = parse_tranque(name)?,
+                Some(("lowest-wins", on)) => tablebase.lowest_wins = on == "on",
+                Some(("positions", count)) => positions = Some(count.parse::<usize>()?),
+                _ => return Err(anyhow!("Unknown tablebase header {}", word)),
+            }
+        }
+
+        if tablebase.tiles > MAX_TILES {
+            return Err(anyhow!("Tablebase of {} tiles", tablebase.tiles));
+        }
+
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        if Some(bytes.len()) != positions.map(|positions| positions * 10) {
+            return Err(anyhow!("Truncated tablebase".to_string()));
+        }
+        tablebase.records = bytes
+            .chunks_exact(10)
+            .map(|record| {
+                let (key, value) = record.split_at(8);
+                (
+                    u64::from_le_bytes(key.try_into().unwrap()),
+                    i16::from_le_bytes(value.try_into().unwrap()),
+                )
+            })
+            .collect();
+
+        let sorted = tablebase
+            .records
+            .windows(2)
+            .all(|pair| pair[0].0 < pair[1].0);
+        let valid = tablebase
+            .records
+            .iter()
+            .all(|(key, _)| valid(*key, tablebase.tiles));
+        if !sorted || !valid {
+            return Err(anyhow!("Corrupt tablebase".to_string()));
+        }
+
+        Ok(tablebase)
+    }
+}
+
+/// The tile with the given index in a double-six set.
+fn tile(index: usize) -> Domino {
+    let high = (0..7)
+        .rev()
+        .find(|high| high * (high + 1) / 2 <= index)
+        .unwrap();
+    Domino(high as i32, (index - high * (high + 1) / 2) as i32)
+}
+
+fn mask(hand: &[Domino]) -> Option<u64> {
+    hand.iter().try_fold(0, |mask, domino| {
+        let index = solver::index(domino) as usize;
+        match index < SET {
+            true => Some(mask | 1 << index),
+            false => None,
+        }
+    })
+}
+
+/// Packs a position: the hand on turn, the other hand, the ends in either
+/// order and whether the other seat has just passed.
+fn key(mover: u64, other: u64, ends: (i32, i32), passed: bool) -> u64 {
+    let ends = solver::index(&Domino(ends.0, ends.1)) as u64;
+    mover | other << SET | ends << (2 * SET) | (passed as u64) << (2 * SET + 5)
+}
+
+/// Whether a key packs two hands apart, with no more than `tiles` tiles
+/// between them, and ends that name a tile.
+fn valid(key: u64, tiles: usize) -> bool {
+    let hand = (1 << SET) - 1;
+    let (mover, other) = (key & hand, key >> SET & hand);
+    let ends = key >> (2 * SET) & 31;
+
+    mover & other == 0
+        && (mover | other).count_ones() as usize <= tiles
+        && ends < SET as u64
+        && key >> (2 * SET + 6) == 0
+}
+
+/// Works out the tablebase a layer at a time, each layer holding the
+/// positions with one tile more in hand than the one before.
+struct Generator {
+    values: HashMap<u64, i16>,
+    /// A finished hand to score, seat 0 having made the last placement.
+    end: Game,
+}
+
+impl Generator {
+    fn new(rules: &Rules) -> Generator {
+        let players = [vec![], vec![], vec![], vec![]];
+        let mut end = Game::from_deal(rules.clone(), 2, 0, players, vec![], 0);
+        end.last = Some(Placement {
+            player: 0,
+            domino: Domino(0, 0),
+            end: End::Left,
+            line_ends: None,
+        });
+
+        Generator {
+            values: HashMap::new(),
+            end,
+        }
+    }
+
+    /// Every position with `count` tiles in hand. The ones where the other
+    /// seat has just passed come first, since passing leads to them.
+    fn layer(&mut self, count: usize) {
+        let mut positions = Vec::new();
+
+        for set in subsets(count) {
+            let mut mover = (set - 1) & set;
+            while mover > 0 {
+                let other = set & !mover;
+                for ends in ends(set) {
+                    positions.push((mover, other, ends));
+                }
+                mover = (mover - 1) & set;
+            }
+        }
+
+        for passed in [true, false] {
+            for (mover, other, ends) in &positions {
+                let value = self.value(*mover, *other, *ends, passed);
+                self.values
+                    .insert(key(*mover, *other, *ends, passed), value as i16);
+            }
+        }
+    }
+
+    fn value(&self, mover: u64, other: u64, (left, right): (i32, i32), passed: bool) -> i32 {
+        let mut best = None;
+
+        for index in (0..SET).filter(|index| mover & 1 << index != 0) {
+            let Domino(a, b) = tile(index);
+            let hand = mover & !(1 << index);
+
+            for (end, open) in [(left, right), (right, left)] {
+                let follows = match (a == end, b == end) {
+                    (true, _) => b,
+                    (_, true) => a,
+                    _ => continue,
+                };
+
+                let value = match hand {
+                    0 => self.score(hand, other, false),
+                    _ => -self.values[&key(other, hand, (follows, open), false)] as i32,
+                };
+                best = best.max(Some(value));
+            }
+        }
+
+        match (best, passed) {
+            (Some(value), _) => value,
+            (None, true) => self.score(mover, other, true),
+            (None, false) => -self.values[&key(other, mover, (left, right), true)] as i32,
+        }
+    }
+
+    /// Value of a finished hand for the seat that made the last placement.
+    fn score(&self, mover: u64, other: u64, blocked: bool) -> i32 {
+        let mut game = self.end.clone();
+        game.players[0] = tiles(mover);
+        game.players[1] = tiles(other);
+
+        let outcome = match blocked {
+            true => Outcome::Blocked,
+            false => Outcome::Won(0),
+        };
+        value(&game, outcome, 0)
+    }
+}
+
+fn tiles(mask: u64) -> Vec<Domino> {
+    (0..SET)
+        .filter(|index| mask & 1 << index != 0)
+        .map(tile)
+        .collect()
+}
+
+/// Every set of `count` tiles, as masks in increasing order.
+fn subsets(count: usize) -> impl Iterator<Item = u64> {
+    let first = (1u64 << count) - 1;
+
+    std::iter::successors(Some(first), |set| {
+        let low = set & set.wrapping_neg();
+        let ripple = set + low;
+        let next = (((ripple ^ set) >> 2) / low) | ripple;
+        (next < 1 << SET).then_some(next)
+    })
+}
+
+/// The open ends a board holding every tile outside the hands can show. A
+/// line of tiles shows every number an even number of times, except for
+/// the numbers at its ends, and each number is on 8 halves of the set.
+fn ends(hands: u64) -> Vec<(i32, i32)> {
+    let mut odd = Vec::new();
+    for number in 0..7 {
+        let halves = tiles(hands)
+            .iter()
+            .map(|tile| (tile.0 == number) as i32 + (tile.1 == number) as i32)
+            .sum::<i32>();
+        if halves % 2 == 1 {
+            odd.push(number);
+        }
+    }
+
+    match odd.as_slice() {
+        [] => (0..7).map(|number| (number, number)).collect(),
+        [left, right] => vec![(*left, *right)],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::seq::SliceRandom;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_tablebase_matches_the_solver() {
+        let rules = Rules::block();
+        let tablebase = Tablebase::generate(&rules, 4).unwrap();
+        let name = format!("rustominoes-test-{}.tablebase", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        tablebase.save(&path).unwrap();
+        let tablebase = Tablebase::load(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut probed = 0;
+        for _ in 0..200 {
+            // Plays at random until few enough tiles are left.
+            let mut game = Game::deal_with(rules.clone(), 2, 0, &mut rng);
+            while game.outcome().is_none()
+                && game.players.iter().map(|hand| hand.len()).sum::<usize>() > 4
+            {
+                let moves = game.legal_moves(game.next as usize);
+                let _ = game.play(moves.choose(&mut rng).unwrap());
+            }
+            if game.outcome().is_some() {
+                continue;
+            }
+
+            let solution = solver::solve(&game).unwrap();
+            assert_eq!(Some(solution.value), tablebase.probe(&game));
+            assert_eq!(Some(solution.moves), tablebase.moves(&game));
+            probed += 1;
+        }
+        assert!(probed > 50);
+
+        assert!(tablebase
+            .probe(&Game::deal_with(rules, 2, 0, &mut rng))
+            .is_none());
+        assert!(!tablebase.covers(&Rules::from_options("block tranque=blocker").unwrap()));
+        assert!(Tablebase::generate(&Rules::block(), MAX_TILES + 1).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_tablebases_are_refused() {
+        let tablebase = Tablebase::generate(&Rules::block(), 3).unwrap();
+        let name = format!("rustominoes-corrupt-{}.tablebase", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        tablebase.save(&path).unwrap();
+        let saved = std::fs::read(&path).unwrap();
+        let header = saved.iter().position(|byte| *byte == b'\n').unwrap() + 1;
+
+        // Records out of order.
+        let mut swapped = saved.clone();
+        let (first, second) = swapped[header..].split_at_mut(10);
+        first.swap_with_slice(&mut second[..10]);
+        // A record missing.
+        let short = saved[..saved.len() - 10].to_vec();
+        // More tiles in hand than the header says.
+        let mut crowded = saved.clone();
+        crowded[header..header + 8].copy_from_slice(&((1u64 << 10) - 1).to_le_bytes());
+
+        for corrupt in [swapped, short, crowded] {
+            std::fs::write(&path, corrupt).unwrap();
+            assert!(Tablebase::load(&path).is_err());
+        }
+        let _ = std::fs::remove_file(&path);
+    }
+}
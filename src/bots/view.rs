@@ -0,0 +1,194 @@
+use crate::game::{Domino, Game, Move, Update, HIDDEN};
+use crate::inference::Tracker;
+use crate::rules::Rules;
+use anyhow::Result;
+use rand::Rng;
+
+/// What a seat knows about the hand being played: its own tiles, the board
+/// and every move made so far. Tiles it cannot see are `HIDDEN`.
+#[derive(Debug, Clone)]
+pub struct PlayerView {
+    pub seat: usize,
+    pub game: Game,
+    /// Every move seen this hand, in order.
+    pub history: Vec<(usize, Update)>,
+    /// What the other seats could be holding.
+    pub tracker: Tracker,
+    /// Tiles this seat drew whose draw has not been broadcast yet.
+    pending: Vec<Domino>,
+}
+
+impl PlayerView {
+    pub fn new(
+        rules: Rules,
+        n_players: i32,
+        hand: usize,
+        seat: usize,
+        tiles: Vec<Domino>,
+    ) -> PlayerView {
+        let game = Game::hidden(rules, n_players, hand, seat, tiles);
+
+        PlayerView {
+            seat,
+            tracker: Tracker::new(&game, Some(seat)),
+            game,
+            history: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn hand(&self) -> &[Domino] {
+        &self.game.players[self.seat]
+    }
+
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.game.legal_moves(self.seat)
+    }
+
+    /// The tile a move of this seat would play.
+    pub fn piece(&self, move_: &Move) -> Option<Domino> {
+        match *move_ {
+            Move::Left(_, pos) | Move::Right(_, pos) | Move::Branch(_, _, pos) => {
+                self.hand().get(pos).copied()
+            }
+            _ => None,
+        }
+    }
+
+    pub fn drawn(&mut self, piece: Domino) {
+        self.pending.push(piece);
+    }
+
+    pub fn apply(&mut self, player: usize, update: &Update) -> Result<()> {
+        let before = self.game.clone();
+        self.game.apply(player, update)?;
+        self.tracker.observe(&before, player, update);
+
+        if let (Update::Draw, true) = (update, player == self.seat) {
+            if let Some(piece) = self.pending.pop() {
+                let hand = &mut self.game.players[self.seat];
+                if let Some(last) = hand.last_mut() {
+                    *last = piece;
+                }
+                self.tracker.reveal(piece);
+            }
+        }
+
+        self.history.push((player, *update));
+        Ok(())
+    }
+
+    /// Tiles on the board, branches included.
+    pub fn played(&self) -> Vec<Domino> {
+        let mut played = self.game.board.clone();
+        for branch in &self.game.branches {
+            played.extend(branch.tiles.iter());
+        }
+        played
+    }
+
+    /// Tiles of the set this seat has not seen: in other hands or the boneyard.
+    pub fn unseen(&self) -> Vec<Domino> {
+        self.tracker.unseen().to_vec()
+    }
+
+    /// Deals the unseen tiles into the hidden hands and the boneyard at
+    /// random, keeping to what the tracker knows.
+    pub fn sample(&self, rng: &mut impl Rng) -> Game {
+        let deal = self.tracker.deal(rng);
+        let mut game = self.game.clone();
+
+        for (hand, dealt) in game.players.iter_mut().zip(deal.hands) {
+            let slots = hand.iter_mut().filter(|domino| **domino == HIDDEN);
+            for (slot, domino) in slots.zip(dealt) {
+                *slot = domino;
+            }
+        }
+        for (slot, domino) in game.boneyard.iter_mut().zip(deal.boneyard) {
+            *slot = domino;
+        }
+
+        game
+    }
+
+    pub fn hand_sizes(&self) -> [usize; 4] {
+        let mut sizes = [0; 4];
+        for (size, hand) in sizes.iter_mut().zip(self.game.players.iter()) {
+            *size = hand.len();
+        }
+        sizes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Rules;
+
+    /// Plays hands with the real engine and checks every seat's view offers
+    /// the same moves the engine does and never rules out a tile another
+    /// seat really holds.
+    #[test]
+    fn test_views_follow_the_game() {
+        let variants = [
+            Rules::block(),
+            Rules::chicken_foot(),
+            Rules::caribbean(),
+            Rules::sniff(),
+            Rules::matador(),
+            Rules::bergen(),
+        ];
+
+        for rules in variants {
+            let mut game = Game::deal(rules.clone(), 4, 0);
+            let mut views = (0..4)
+                .map(|seat| {
+                    let tiles = game.players[seat]
+                        .iter()
+                        .filter(|piece| !game.opening_draws.contains(&(seat, **piece)))
+                        .copied()
+                        .collect();
+                    PlayerView::new(rules.clone(), 4, 0, seat, tiles)
+                })
+                .collect::<Vec<_>>();
+
+            for (seat, piece) in game.opening_draws.clone() {
+                views[seat].drawn(piece);
+                for view in views.iter_mut() {
+                    view.apply(seat, &Update::Draw).unwrap();
+                }
+            }
+
+            let mut turns = 0;
+            while game.outcome().is_none() {
+                let turn = game.next as usize;
+                let moves = game.legal_moves(turn);
+                assert_eq!(moves, views[turn].legal_moves());
+
+                let update = game.play(&moves[turns % moves.len()]).unwrap();
+                if let Update::Draw = update {
+                    views[turn].drawn(*game.players[turn].last().unwrap());
+                }
+                for view in views.iter_mut() {
+                    view.apply(turn, &update).unwrap();
+                }
+
+                assert_eq!(game.players[turn], views[turn].hand());
+                assert_eq!(game.board, views[(turn + 1) % 4].game.board);
+                for (view, seat) in views.iter().zip([1, 2, 3, 0]) {
+                    let tracker = &view.tracker;
+                    assert_eq!(game.players[seat].len(), tracker.hand_size(seat));
+                    assert!(game.players[seat]
+                        .iter()
+                        .all(|domino| tracker.could_hold(seat, domino)));
+                }
+                turns += 1;
+            }
+
+            for view in &views {
+                assert_eq!(game.outcome(), view.game.outcome());
+                assert_eq!(game.points, view.game.points);
+            }
+        }
+    }
+}
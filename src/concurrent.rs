@@ -1,87 +1,907 @@
+use crate::bots::heuristics::GreedyStrategy;
+use crate::bots::player::Strategy;
+use crate::bots::view::PlayerView;
+use crate::forecast::{Chances, Forecast};
 use crate::game::*;
+use crate::hints::{self, Hint};
+use crate::log::{Event, GameLog};
+use crate::rules::{Misdeal, Rules};
+use crate::scoring::{HandResult, Match};
 use async_trait::async_trait;
-use tokio::sync::mpsc::{channel, Sender};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::{channel, unbounded_channel, Receiver, Sender, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::{timeout_at, Duration, Instant};
 
 #[derive(Debug)]
 pub enum Message<'a> {
     Init((&'a Vec<Domino>, usize)),
-    YourTurn,
-    Update(Update),
+    /// The time the player has to move, when the table is timed.
+    YourTurn(Option<TimeLeft>),
+    /// A move made at the table: the seat, what it did to the board and what
+    /// it scored.
+    Update((usize, Update, Option<Score>)),
+    Drawn(Domino),
+    HandOver(HandResult),
+    /// The cards were thrown in because of a seat's hand and dealt again.
+    Misdeal((usize, Misdeal)),
+    /// The player's legal moves, best first.
+    Hints(&'a [Hint]),
+    /// Coach warning: the best move and the much worse one asked for, which
+    /// is only made if asked for again.
+    Blunder((Hint, Hint)),
+    /// The seat ran out of time and its side gave the match up.
+    Forfeit(usize),
+}
+
+/// What the spectators of a table are sent.
+#[derive(Debug, Clone)]
+pub enum Watch {
+    /// A hand was dealt, with how many tiles each seat holds.
+    Deal(Vec<usize>),
+    /// A move made at the table, as the players are sent it.
+    Update((usize, Update, Option<Score>)),
+    /// The win bar, worked out again after the moves so far.
+    Chances(Chances),
+    HandOver(HandResult),
+    Forfeit(usize),
+    /// The match is over, with the final scores.
+    Over([i32; 4]),
+}
+
+/// Time left to a player on its turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeLeft {
+    /// To make this move.
+    pub move_: Duration,
+    /// On the player's clock for the rest of the match, if it has one.
+    pub clock: Option<Duration>,
+}
+
+/// Limits on the time players take to move, like a chess clock: each move
+/// has its own limit and each player a clock that runs only on its turns.
+/// Either is left out when `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Clock {
+    pub per_move: Option<Duration>,
+    /// Time each player has for all its moves of the match.
+    pub per_match: Option<Duration>,
+    pub timeout: Timeout,
+}
+
+/// What happens when a player runs out of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timeout {
+    /// The seat passes, or draws, if it may; otherwise it plays as a bot.
+    #[default]
+    Pass,
+    /// A bot makes the move for the seat.
+    Bot,
+    /// The seat's side loses the match.
+    Forfeit,
+}
+
+impl Timeout {
+    pub fn parse(name: &str) -> Option<Timeout> {
+        match name {
+            "pass" => Some(Timeout::Pass),
+            "bot" => Some(Timeout::Bot),
+            "forfeit" => Some(Timeout::Forfeit),
+            _ => None,
+        }
+    }
+}
+
+/// What a player may send on its turn.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Request {
+    Move(Move),
+    Hint,
+    /// Turns coach mode on or off.
+    Coach(bool),
+    /// The player is gone. Its moves are made as if it ran out of time.
+    Leave,
 }
 
 #[async_trait]
 pub trait RemotePlayer: Send + Sync {
     async fn send_message<'a>(&mut self, message: Message<'a>);
     async fn read_move(&mut self) -> Move;
+    /// Players that can ask for hints read more than moves.
+    async fn read_request(&mut self) -> Request {
+        Request::Move(self.read_move().await)
+    }
     fn number(&self) -> usize;
     fn set_number(&mut self, number: usize);
 }
 
-pub fn start_game() -> Sender<Box<dyn RemotePlayer>> {
-    let (tx, mut rx) = channel::<Box<dyn RemotePlayer>>(4);
+pub fn start_game(rules: Rules) -> Sender<Box<dyn RemotePlayer>> {
+    spawn_table(rules, 4, None, Clock::default()).0
+}
+
+/// Spectators look on a table through it.
+pub type Spectators = broadcast::Sender<Watch>;
 
-    tokio::spawn(async move {
+/// Bots that take the seats left empty when a table has waited long enough
+/// for players. Players who come later take over from them between hands.
+pub struct Fill {
+    pub wait: Duration,
+    /// Makes a bot for an empty seat.
+    pub bot: Box<dyn Fn() -> Box<dyn RemotePlayer> + Send + Sync>,
+}
+
+/// Opens a table for `n_players`, returning where to send the players, the
+/// task that plays the match once every seat is taken, by players or, after
+/// the wait, by the bots of `fill`, and what its spectators subscribe to.
+/// The task ends without a match if the table is given up before it fills.
+/// Turns are timed by `clock`.
+pub fn spawn_table(
+    rules: Rules,
+    n_players: usize,
+    fill: Option<Fill>,
+    clock: Clock,
+) -> (
+    Sender<Box<dyn RemotePlayer>>,
+    JoinHandle<Option<Match>>,
+    Spectators,
+) {
+    let (tx, mut rx) = channel::<Box<dyn RemotePlayer>>(n_players);
+    let (spectators, _) = broadcast::channel(64);
+    let watch = spectators.clone();
+
+    let task = tokio::spawn(async move {
         let mut players = Vec::<Box<dyn RemotePlayer>>::new();
+        let deadline = fill.as_ref().map(|fill| Instant::now() + fill.wait);
 
-        loop {
-            let player_opt = rx.recv().await;
-            if let Some(mut player) = player_opt {
-                player.set_number(players.len());
-                players.push(player);
+        while players.len() < n_players {
+            let mut player = match deadline {
+                Some(deadline) => match timeout_at(deadline, rx.recv()).await {
+                    Ok(player) => player?,
+                    Err(_) => break,
+                },
+                None => rx.recv().await?,
+            };
+            player.set_number(players.len());
+            players.push(player);
+        }
+
+        let mut bots = vec![false; players.len()];
+        if let Some(fill) = &fill {
+            while players.len() < n_players {
+                players.push((fill.bot)());
+                bots.push(true);
+            }
+        }
+
+        let mut seats = Seats::new(players);
+        seats.bots = bots;
+        seats.clock = clock;
+        seats.rail = Some(Rail::new(watch));
+        match fill {
+            Some(_) => seats.arrivals = Some(rx),
+            None => rx.close(),
+        }
+
+        Some(seats.play_match(rules).await)
+    });
+
+    (tx, task, spectators)
+}
+
+/// What a table sends its spectators, with the win bar after every move.
+/// The bar is worked out off the table's task, and only while somebody is
+/// watching; moves made while it is being worked out are taken in together.
+struct Rail {
+    spectators: Spectators,
+    forecast: UnboundedSender<Feed>,
+}
+
+enum Feed {
+    /// The match before a hand, and the hand just dealt.
+    Deal(Box<(Match, Game)>),
+    Update(usize, Update),
+}
+
+impl Rail {
+    fn new(spectators: Spectators) -> Rail {
+        let (forecast, mut feed) = unbounded_channel::<Feed>();
+        let watch = spectators.clone();
+
+        tokio::spawn(async move {
+            let mut forecast: Option<Forecast> = None;
+
+            while let Some(first) = feed.recv().await {
+                let mut next = Some(first);
+                while let Some(item) = next {
+                    match item {
+                        Feed::Deal(deal) => {
+                            forecast = Some(Forecast::new(&deal.0, &deal.1, rand::random()))
+                        }
+                        Feed::Update(player, update) => {
+                            let applied = forecast.as_mut().map(|f| f.apply(player, &update));
+                            if let Some(Err(_)) = applied {
+                                forecast = None;
+                            }
+                        }
+                    }
+                    next = feed.try_recv().ok();
+                }
+
+                if watch.receiver_count() == 0 {
+                    continue;
+                }
+                let Some(mut current) = forecast.take() else {
+                    continue;
+                };
+                let estimate = tokio::task::spawn_blocking(move || {
+                    let chances = current.chances();
+                    (current, chances)
+                });
+                let Ok((current, chances)) = estimate.await else {
+                    continue;
+                };
+                forecast = Some(current);
+                let _ = watch.send(Watch::Chances(chances));
+            }
+        });
+
+        Rail {
+            spectators,
+            forecast,
+        }
+    }
+
+    fn send(&self, watch: Watch) {
+        let _ = self.spectators.send(watch);
+    }
+
+    fn deal(&self, match_: &Match, game: &Game) {
+        let tiles = game.players[..game.n_players() as usize]
+            .iter()
+            .map(|hand| hand.len())
+            .collect();
+        self.send(Watch::Deal(tiles));
+        let _ = self
+            .forecast
+            .send(Feed::Deal(Box::new((match_.clone(), game.clone()))));
+    }
+
+    fn moved(&self, turn: usize, update: Update) {
+        let _ = self.forecast.send(Feed::Update(turn, update));
+    }
+}
+
+/// Plays a whole match between the given seats and returns the final score.
+pub async fn play_match(players: Vec<Box<dyn RemotePlayer>>, rules: Rules) -> Match {
+    Seats::new(players).play_match(rules).await
+}
+
+/// The players of a match, in seat order.
+struct Seats {
+    players: Vec<Box<dyn RemotePlayer>>,
+    coached: Vec<bool>,
+    /// Seats held by bots until a player comes.
+    bots: Vec<bool>,
+    /// Where players who come after the match started are sent.
+    arrivals: Option<Receiver<Box<dyn RemotePlayer>>>,
+    clock: Clock,
+    /// Time left on each seat's clock.
+    banks: Vec<Option<Duration>>,
+    rail: Option<Rail>,
+}
+
+impl Seats {
+    fn new(players: Vec<Box<dyn RemotePlayer>>) -> Seats {
+        let n_players = players.len();
+
+        Seats {
+            players,
+            coached: vec![false; n_players],
+            bots: vec![false; n_players],
+            arrivals: None,
+            clock: Clock::default(),
+            banks: Vec::new(),
+            rail: None,
+        }
+    }
+
+    async fn play_match(mut self, rules: Rules) -> Match {
+        let players = &mut self.players;
+        let coached = &mut self.coached;
+        let n_players = players.len();
+        let mut match_ = Match::new(rules, n_players as i32);
+        let mut rotation = 0;
+        self.banks = vec![self.clock.per_match; n_players];
+
+        while !match_.is_over() {
+            // Players who came since the last hand take over from bots.
+            if let Some(arrivals) = self.arrivals.as_mut() {
+                while let Some(seat) = self.bots.iter().position(|bot| *bot) {
+                    match arrivals.try_recv() {
+                        Ok(player) => {
+                            players[seat] = player;
+                            coached[seat] = false;
+                            self.bots[seat] = false;
+                        }
+                        Err(_) => break,
+                    }
+                }
+            }
+
+            // Players are kept in seat order and move round in duplicate matches.
+            let shift = (match_.rotation() + n_players - rotation) % n_players;
+            players.rotate_right(shift);
+            coached.rotate_right(shift);
+            self.bots.rotate_right(shift);
+            self.banks.rotate_right(shift);
+            rotation = match_.rotation();
+            for (seat, player) in players.iter_mut().enumerate() {
+                player.set_number(seat);
+            }
+
+            let mut game = match_.deal();
+            if let Some(rail) = &self.rail {
+                rail.deal(&match_, &game);
             }
 
-            if players.len() == 4 {
+            for misdeal in &match_.misdeals {
+                for player in players.iter_mut() {
+                    player.send_message(Message::Misdeal(*misdeal)).await;
+                }
+            }
+
+            let mut table = Table {
+                players,
+                coached,
+                views: Vec::new(),
+                clock: self.clock,
+                banks: &mut self.banks,
+                rail: self.rail.as_ref(),
+            };
+            let outcome = table
+                .play_hand(&mut game, match_.board(), &mut match_.log)
+                .await;
+
+            let Some(outcome) = outcome else {
+                let seat = game.next as usize;
+                match_.forfeit(seat);
+                for player in players.iter_mut() {
+                    player.send_message(Message::Forfeit(seat)).await;
+                }
+                if let Some(rail) = &self.rail {
+                    rail.send(Watch::Forfeit(seat));
+                }
                 break;
+            };
+            let result = match_.record(&game, outcome);
+            if let Some(rail) = &self.rail {
+                rail.send(Watch::HandOver(result));
+            }
+
+            for player in players.iter_mut() {
+                player.send_message(Message::HandOver(result)).await;
             }
         }
 
-        rx.close();
+        if let Some(rail) = &self.rail {
+            rail.send(Watch::Over(match_.scores));
+        }
 
-        start_game_loop(players).await;
-    });
+        match_
+    }
+}
 
-    tx
+/// The seats of a hand being played, with what each of them can see, for
+/// the hints and for the moves made for seats out of time.
+struct Table<'a> {
+    players: &'a mut [Box<dyn RemotePlayer>],
+    coached: &'a mut [bool],
+    views: Vec<PlayerView>,
+    clock: Clock,
+    banks: &'a mut [Option<Duration>],
+    rail: Option<&'a Rail>,
 }
 
-async fn start_game_loop(mut players: Vec<Box<dyn RemotePlayer>>) {
-    let mut game = Game::new(players.len() as i32);
+impl Table<'_> {
+    /// Plays the hand out, unless the seat on turn forfeits the match.
+    async fn play_hand(
+        &mut self,
+        game: &mut Game,
+        board: usize,
+        log: &mut GameLog,
+    ) -> Option<Outcome> {
+        let n_players = self.players.len() as i32;
 
-    for player in &mut players {
-        let pieces = &game.players[player.number()];
-        player
-            .send_message(Message::Init((pieces, player.number())))
-            .await;
+        for player in self.players.iter_mut() {
+            let seat = player.number();
+            let pieces = game.players[seat]
+                .iter()
+                .filter(|piece| !game.opening_draws.contains(&(seat, **piece)))
+                .copied()
+                .collect::<Vec<_>>();
+            player.send_message(Message::Init((&pieces, seat))).await;
+
+            let view = PlayerView::new(game.rules.clone(), n_players, board, seat, pieces);
+            self.views.push(view);
+        }
+
+        for (seat, piece) in game.opening_draws.clone() {
+            self.players[seat].send_message(Message::Drawn(piece)).await;
+            self.broadcast(seat, Update::Draw, None, Some(piece)).await;
+        }
+
+        loop {
+            if let Some(outcome) = game.outcome() {
+                return Some(outcome);
+            }
+
+            let turn = game.next as usize;
+            let time_left = self.time_left(turn);
+            self.players[turn]
+                .send_message(Message::YourTurn(time_left))
+                .await;
+
+            let update = self.read_move(game, turn, log, time_left).await?;
+            let piece = game.players[turn].last().copied();
+
+            if let (Update::Draw, Some(piece)) = (update, piece) {
+                self.players[turn].send_message(Message::Drawn(piece)).await;
+            }
+
+            println!("{:?}", game.board);
+
+            self.broadcast(turn, update, game.scored, piece).await;
+            if let Some(rail) = self.rail {
+                rail.moved(turn, update);
+            }
+        }
     }
 
-    loop {
-        let turn = game.next as usize;
-        let player = &mut players[turn];
-        let mut move_: Move;
-        let update: Update;
+    fn time_left(&self, turn: usize) -> Option<TimeLeft> {
+        let clock = self.banks[turn];
+        let move_ = match (self.clock.per_move, clock) {
+            (Some(per_move), Some(clock)) => per_move.min(clock),
+            (per_move, clock) => per_move.or(clock)?,
+        };
+
+        Some(TimeLeft { move_, clock })
+    }
 
-        player.send_message(Message::YourTurn).await;
+    /// Answers the player on turn until it makes a legal move, which is
+    /// played. In coach mode a blunder is only played once confirmed. Once
+    /// the time left is up, or the player left, the move is made for it, or `None`
+    /// returned if the player forfeits; the time taken by the searches for
+    /// hints and warnings is not counted.
+    async fn read_move(
+        &mut self,
+        game: &mut Game,
+        turn: usize,
+        log: &mut GameLog,
+        time_left: Option<TimeLeft>,
+    ) -> Option<Update> {
+        let mut warned = None;
+        let started = Instant::now();
+        let mut deadline = time_left.map(|time_left| started + time_left.move_);
+        let mut searched = Duration::ZERO;
 
         loop {
-            move_ = player.read_move().await;
+            let player = &mut self.players[turn];
 
-            let outcome = game.play(&move_);
-            match outcome {
-                Ok(up) => {
-                    update = up;
-                    break;
+            let request = match deadline {
+                Some(deadline) => timeout_at(deadline, player.read_request()).await.ok(),
+                None => Some(player.read_request().await),
+            };
+
+            let move_ = match request {
+                None | Some(Request::Leave) => {
+                    self.charge(turn, started.elapsed().saturating_sub(searched));
+                    let move_ = self.timed_out(game, turn)?;
+                    let update = game.play(&move_).ok()?;
+                    log.push(Event::Move(move_));
+                    return Some(update);
+                }
+                Some(Request::Move(move_)) => move_,
+                Some(Request::Hint) => {
+                    let hints = self.hints(turn, log, &mut deadline, &mut searched).await;
+                    self.players[turn]
+                        .send_message(Message::Hints(&hints))
+                        .await;
+                    continue;
+                }
+                Some(Request::Coach(on)) => {
+                    self.coached[turn] = on;
+                    continue;
+                }
+            };
+
+            let legal = game.legal_moves(turn).contains(&move_);
+            if legal && self.coached[turn] && warned != Some(move_) {
+                let hints = self.hints(turn, log, &mut deadline, &mut searched).await;
+
+                if let Some(blunder) = hints::blunder(&hints, &move_) {
+                    warned = Some(move_);
+                    self.players[turn]
+                        .send_message(Message::Blunder(blunder))
+                        .await;
+                    continue;
                 }
-                _ => {}
+            }
+
+            if let Ok(update) = game.play(&move_) {
+                self.charge(turn, started.elapsed().saturating_sub(searched));
+                log.push(Event::Move(move_));
+                return Some(update);
             }
         }
+    }
 
-        println!("{:?}", game.board);
+    /// Ranks the moves of the seat on turn. The search runs off the async
+    /// tasks and stops the seat's time while it lasts.
+    async fn hints(
+        &self,
+        turn: usize,
+        log: &GameLog,
+        deadline: &mut Option<Instant>,
+        searched: &mut Duration,
+    ) -> Vec<Hint> {
+        let view = self.views[turn].clone();
+        let seed = log.events.len() as u64;
+        let started = Instant::now();
 
-        for i in 0..players.len() {
-            if i == turn {
-                continue;
+        let hints = tokio::task::spawn_blocking(move || hints::hints(&view, seed))
+            .await
+            .unwrap();
+
+        let spent = started.elapsed();
+        *searched += spent;
+        if let Some(deadline) = deadline.as_mut() {
+            *deadline += spent;
+        }
+
+        hints
+    }
+
+    /// Takes the time of a turn off the seat's clock.
+    fn charge(&mut self, turn: usize, spent: Duration) {
+        if let Some(bank) = self.banks[turn].as_mut() {
+            *bank = bank.saturating_sub(spent);
+        }
+    }
+
+    /// The move made for a seat out of time, if it does not forfeit.
+    fn timed_out(&self, game: &Game, turn: usize) -> Option<Move> {
+        let legal = game.legal_moves(turn);
+        let fallback = [Move::Pass(turn), Move::Draw(turn)]
+            .into_iter()
+            .find(|move_| legal.contains(move_));
+
+        match (self.clock.timeout, fallback) {
+            (Timeout::Forfeit, _) => None,
+            (Timeout::Pass, Some(move_)) => Some(move_),
+            _ => {
+                let move_ = GreedyStrategy.choose(&self.views[turn]);
+                let move_ = legal.contains(&move_).then_some(move_);
+                move_.or(fallback).or(legal.first().copied())
             }
+        }
+    }
+
+    /// Sends a move to every seat. `drawn` is the tile the mover drew, if
+    /// that was the move.
+    async fn broadcast(
+        &mut self,
+        turn: usize,
+        update: Update,
+        score: Option<Score>,
+        drawn: Option<Domino>,
+    ) {
+        if let (Update::Draw, Some(piece)) = (update, drawn) {
+            self.views[turn].drawn(piece);
+        }
+
+        for view in self.views.iter_mut() {
+            let _ = view.apply(turn, &update);
+        }
+
+        for player in self.players.iter_mut() {
+            player
+                .send_message(Message::Update((turn, update, score)))
+                .await;
+        }
+        if let Some(rail) = self.rail {
+            rail.send(Watch::Update((turn, update, score)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::heuristics::{greedy_bot, random_bot};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::sync::oneshot;
+
+    /// Asks for a hint before every move and plays the best one, after a
+    /// delay.
+    struct HintTaker {
+        number: usize,
+        asked: bool,
+        best: Option<Move>,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl RemotePlayer for HintTaker {
+        async fn send_message<'a>(&mut self, message: Message<'a>) {
+            if let Message::Hints(hints) = message {
+                self.best = hints.first().map(|hint| hint.move_);
+            }
+        }
+
+        async fn read_move(&mut self) -> Move {
+            tokio::time::sleep(self.delay).await;
+            self.best.take().unwrap_or(Move::Pass(self.number))
+        }
+
+        async fn read_request(&mut self) -> Request {
+            self.asked = !self.asked;
+            match self.asked {
+                true => Request::Hint,
+                false => Request::Move(self.read_move().await),
+            }
+        }
+
+        fn number(&self) -> usize {
+            self.number
+        }
+
+        fn set_number(&mut self, number: usize) {
+            self.number = number;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hints_on_request() {
+        let rules = Rules::from_options("block hands=1").unwrap();
+        let players = vec![
+            Box::new(HintTaker {
+                number: 0,
+                asked: false,
+                best: None,
+                delay: Duration::ZERO,
+            }) as Box<dyn RemotePlayer>,
+            greedy_bot(rules.clone(), 3),
+            random_bot(rules.clone(), 3, 1),
+        ];
+
+        let match_ = play_match(players, rules).await;
+
+        assert!(match_.is_over());
+    }
+
+    #[tokio::test]
+    async fn test_hints_stop_the_clock() {
+        let rules = Rules::from_options("block hands=1").unwrap();
+        let clock = Clock {
+            per_move: Some(Duration::from_millis(80)),
+            per_match: None,
+            timeout: Timeout::Forfeit,
+        };
+        let (seats, task, _) = spawn_table(rules.clone(), 2, None, clock);
+
+        let _ = seats
+            .send(Box::new(HintTaker {
+                number: 0,
+                asked: false,
+                best: None,
+                delay: Duration::from_millis(50),
+            }))
+            .await;
+        let _ = seats.send(greedy_bot(rules, 2)).await;
+
+        let match_ = task.await.unwrap().unwrap();
+        assert_eq!(None, match_.forfeited);
+    }
+
+    /// Plays at random, counting the hands dealt to it and, if told to,
+    /// holding its first move until let go.
+    struct Latecomer {
+        bot: Box<dyn RemotePlayer>,
+        hands: Arc<AtomicUsize>,
+        hold: Option<oneshot::Receiver<()>>,
+    }
+
+    #[async_trait]
+    impl RemotePlayer for Latecomer {
+        async fn send_message<'a>(&mut self, message: Message<'a>) {
+            if let Message::Init(_) = message {
+                self.hands.fetch_add(1, Ordering::SeqCst);
+            }
+            self.bot.send_message(message).await;
+        }
+
+        async fn read_move(&mut self) -> Move {
+            if let Some(hold) = self.hold.take() {
+                let _ = hold.await;
+            }
+            self.bot.read_move().await
+        }
 
-            players[i].send_message(Message::Update(update)).await;
+        fn number(&self) -> usize {
+            self.bot.number()
+        }
+
+        fn set_number(&mut self, number: usize) {
+            self.bot.set_number(number);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_bots_fill_empty_seats() {
+        let rules = Rules::from_options("block hands=3").unwrap();
+        let fill = Fill {
+            wait: Duration::from_millis(50),
+            bot: Box::new(|| greedy_bot(Rules::block(), 4)),
+        };
+        let (seats, task, _) = spawn_table(rules.clone(), 4, Some(fill), Clock::default());
+
+        let (go, hold) = oneshot::channel();
+        let first = Arc::new(AtomicUsize::new(0));
+        let late = Arc::new(AtomicUsize::new(0));
+        let _ = seats
+            .send(Box::new(Latecomer {
+                bot: random_bot(rules.clone(), 4, 1),
+                hands: first.clone(),
+                hold: Some(hold),
+            }))
+            .await;
+
+        // The bots sit down and the first hand waits on the only player,
+        // while another one comes to take over a bot from the next hand.
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let _ = seats
+            .send(Box::new(Latecomer {
+                bot: random_bot(rules.clone(), 4, 2),
+                hands: late.clone(),
+                hold: None,
+            }))
+            .await;
+        let _ = go.send(());
+
+        let match_ = task.await.unwrap().unwrap();
+
+        assert!(match_.is_over());
+        assert_eq!(3, first.load(Ordering::SeqCst));
+        assert_eq!(2, late.load(Ordering::SeqCst));
+    }
+
+    /// Never makes a move.
+    struct Idle {
+        number: usize,
+    }
+
+    #[async_trait]
+    impl RemotePlayer for Idle {
+        async fn send_message<'a>(&mut self, _message: Message<'a>) {}
+
+        async fn read_move(&mut self) -> Move {
+            std::future::pending().await
+        }
+
+        fn number(&self) -> usize {
+            self.number
+        }
+
+        fn set_number(&mut self, number: usize) {
+            self.number = number;
+        }
+    }
+
+    async fn play_timed(timeout: Timeout) -> Match {
+        let rules = Rules::from_options("block hands=2").unwrap();
+        let clock = Clock {
+            per_move: Some(Duration::from_millis(20)),
+            per_match: Some(Duration::from_secs(60)),
+            timeout,
+        };
+        let (seats, task, _) = spawn_table(rules.clone(), 3, None, clock);
+
+        let _ = seats.send(Box::new(Idle { number: 0 })).await;
+        let _ = seats.send(greedy_bot(rules.clone(), 3)).await;
+        let _ = seats.send(random_bot(rules, 3, 1)).await;
+
+        task.await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_moves_made_for_idle_players() {
+        for timeout in [Timeout::Pass, Timeout::Bot] {
+            let match_ = play_timed(timeout).await;
+
+            assert!(match_.is_over());
+            assert_eq!(None, match_.forfeited);
+            assert_eq!(2, match_.hand);
+        }
+
+        let match_ = play_timed(Timeout::Forfeit).await;
+        assert_eq!(Some(0), match_.forfeited);
+        assert!(match_.is_over());
+        assert_ne!(Some(0), match_.winner());
+    }
+
+    /// Hung up before the first move.
+    struct Gone {
+        number: usize,
+    }
+
+    #[async_trait]
+    impl RemotePlayer for Gone {
+        async fn send_message<'a>(&mut self, _message: Message<'a>) {}
+
+        async fn read_move(&mut self) -> Move {
+            Move::Pass(self.number)
+        }
+
+        async fn read_request(&mut self) -> Request {
+            Request::Leave
+        }
+
+        fn number(&self) -> usize {
+            self.number
+        }
+
+        fn set_number(&mut self, number: usize) {
+            self.number = number;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_moves_made_for_players_who_left() {
+        let rules = Rules::from_options("block hands=2").unwrap();
+        let (seats, task, _) = spawn_table(rules.clone(), 3, None, Clock::default());
+
+        let _ = seats.send(Box::new(Gone { number: 0 })).await;
+        let _ = seats.send(greedy_bot(rules.clone(), 3)).await;
+        let _ = seats.send(random_bot(rules, 3, 1)).await;
+
+        let match_ = task.await.unwrap().unwrap();
+        assert!(match_.is_over());
+        assert_eq!(None, match_.forfeited);
+        assert_eq!(2, match_.hand);
+    }
+
+    #[tokio::test]
+    async fn test_spectators_see_the_win_bar() {
+        let rules = Rules::from_options("block hands=1").unwrap();
+        let (seats, task, spectators) = spawn_table(rules.clone(), 2, None, Clock::default());
+        let mut watching = spectators.subscribe();
+        drop(spectators);
+        let spectator = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            while let Ok(watch) = watching.recv().await {
+                seen.push(watch);
+            }
+            seen
+        });
+
+        let _ = seats.send(greedy_bot(rules.clone(), 2)).await;
+        let _ = seats.send(random_bot(rules, 2, 1)).await;
+        let match_ = task.await.unwrap().unwrap();
+        let seen = spectator.await.unwrap();
+
+        assert!(matches!(seen[0], Watch::Deal(ref tiles) if *tiles == [14, 14]));
+        assert!(seen.iter().any(|watch| matches!(watch, Watch::Update(_))));
+        assert!(seen
+            .iter()
+            .any(|watch| matches!(watch, Watch::Over(scores) if *scores == match_.scores)));
+        for watch in &seen {
+            if let Watch::Chances(chances) = watch {
+                assert_eq!(2, chances.sides.len());
+                let hand = chances.sides.iter().map(|side| side.hand).sum::<f64>();
+                assert!(hand <= 1.0 + 1e-9);
+            }
         }
+        assert!(seen.iter().any(|watch| matches!(watch, Watch::Chances(_))));
     }
 }
@@ -0,0 +1,287 @@
+use crate::analysis::{replay, Step};
+use crate::bots::playout::random_playout;
+use crate::game::{Game, Move, Outcome, Update, HIDDEN};
+use crate::inference::Tracker;
+use crate::log::{Event, GameLog};
+use crate::rules::Rules;
+use crate::scoring::Match;
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::fmt;
+
+/// Chances of a side of the table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Side {
+    pub seats: Vec<usize>,
+    /// Chance of winning the hand being played.
+    pub hand: f64,
+    /// Chance of winning the match.
+    pub match_: f64,
+}
+
+/// Chances of every side, as a spectator can tell them. Tied blocked hands
+/// and matches won by nobody, or not over within the hands simulated, are
+/// not counted for anyone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chances {
+    pub sides: Vec<Side>,
+}
+
+/// Follows a hand from the rail, seeing only what everybody at the table
+/// sees, and estimates who wins it and the match by dealing the unseen
+/// tiles at random and playing the hand and the rest of the match out.
+///
+/// Every estimate plays `samples` deals to the end of the hand and up to
+/// `hands` more hands each, which takes a while: tables work it out for
+/// their spectators off their own task, as `concurrent::Watch::Chances`.
+pub struct Forecast {
+    /// The match as it stood before the hand.
+    match_: Match,
+    game: Game,
+    tracker: Tracker,
+    rng: StdRng,
+    /// Deals sampled for each estimate.
+    pub samples: usize,
+    /// Hands played at most after the current one to finish the match;
+    /// matches still going then are not counted for anyone.
+    pub hands: usize,
+}
+
+impl Forecast {
+    /// Starts following a hand of the match just dealt. Only the size of
+    /// the hands is read from the game.
+    pub fn new(match_: &Match, game: &Game, seed: u64) -> Forecast {
+        let mut public = game.clone();
+        for hand in public.players.iter_mut() {
+            hand.fill(HIDDEN);
+        }
+        public.boneyard.fill(HIDDEN);
+
+        let mut match_ = match_.clone();
+        match_.log = GameLog::new();
+
+        Forecast {
+            match_,
+            tracker: Tracker::new(&public, None),
+            game: public,
+            rng: StdRng::seed_from_u64(seed),
+            samples: 200,
+            hands: 50,
+        }
+    }
+
+    /// Takes in a move seen at the table.
+    pub fn apply(&mut self, player: usize, update: &Update) -> Result<()> {
+        let before = self.game.clone();
+        self.game.apply(player, update)?;
+        self.tracker.observe(&before, player, update);
+        Ok(())
+    }
+
+    pub fn chances(&mut self) -> Chances {
+        let rules = self.game.rules.clone();
+        let n_players = self.game.n_players() as usize;
+        let mut hand = [0.0; 4];
+        let mut match_ = [0.0; 4];
+        let share = 1.0 / self.samples.max(1) as f64;
+
+        for _ in 0..self.samples {
+            let mut world = self.sample();
+            let outcome = match world.outcome() {
+                Some(outcome) => outcome,
+                None => random_playout(&mut world, &mut self.rng),
+            };
+
+            let winner = match outcome {
+                Outcome::Won(seat) => Some(seat),
+                Outcome::Blocked => world.blocked_winner(),
+            };
+            if let Some(winner) = winner {
+                hand[winner] += share;
+            }
+
+            if let Some(player) = self.finish(&world, outcome) {
+                match_[self.match_.seat(player)] += share;
+            }
+        }
+
+        let mut sides: Vec<Side> = Vec::new();
+        for seat in 0..n_players {
+            match sides
+                .iter_mut()
+                .find(|side| rules.same_side(n_players, side.seats[0], seat))
+            {
+                Some(side) => side.seats.push(seat),
+                None => sides.push(Side {
+                    seats: vec![seat],
+                    hand: 0.0,
+                    match_: 0.0,
+                }),
+            }
+        }
+        for side in sides.iter_mut() {
+            side.hand = side.seats.iter().map(|seat| hand[*seat]).sum();
+            side.match_ = side.seats.iter().map(|seat| match_[*seat]).sum();
+        }
+
+        Chances { sides }
+    }
+
+    /// Deals the unseen tiles into the hidden hands and the boneyard.
+    fn sample(&mut self) -> Game {
+        let deal = self.tracker.deal(&mut self.rng);
+        let mut game = self.game.clone();
+
+        for (hand, dealt) in game.players.iter_mut().zip(deal.hands) {
+            let slots = hand.iter_mut().filter(|domino| **domino == HIDDEN);
+            for (slot, domino) in slots.zip(dealt) {
+                *slot = domino;
+            }
+        }
+        for (slot, domino) in game.boneyard.iter_mut().zip(deal.boneyard) {
+            *slot = domino;
+        }
+
+        game
+    }
+
+    /// Plays the rest of the match out at random after the hand, returning
+    /// the player who wins it.
+    fn finish(&mut self, game: &Game, outcome: Outcome) -> Option<usize> {
+        let mut match_ = self.match_.clone();
+        match_.record(game, outcome);
+
+        for _ in 0..self.hands {
+            if match_.is_over() {
+                break;
+            }
+            let mut game = match_.deal_with(&mut self.rng);
+            let outcome = random_playout(&mut game, &mut self.rng);
+            match_.record(&game, outcome);
+        }
+
+        match_.winner()
+    }
+}
+
+/// Replays a recorded match played under `rules` as a spectator would
+/// have watched it, with the chances after every move.
+pub fn follow(
+    rules: &Rules,
+    log: &GameLog,
+    samples: usize,
+    seed: u64,
+) -> Result<Vec<(Move, Chances)>> {
+    let n_players = log
+        .events
+        .iter()
+        .find_map(|event| match event {
+            Event::Deal { players, .. } => {
+                Some(players.iter().filter(|tiles| !tiles.is_empty()).count())
+            }
+            _ => None,
+        })
+        .unwrap_or(2);
+
+    let mut match_ = Match::new(rules.clone(), n_players as i32);
+    let mut forecast: Option<Forecast> = None;
+    let mut chances = Vec::new();
+
+    replay(rules, log, |replay, step| match step {
+        Step::Move(move_, index) => {
+            let forecast = forecast.get_or_insert_with(|| {
+                let mut forecast = Forecast::new(&match_, &replay.game, seed ^ index as u64);
+                forecast.samples = samples;
+                forecast
+            });
+
+            let mut game = replay.game.clone();
+            if let Ok(update) = game.play(move_) {
+                if forecast.apply(move_.player(), &update).is_ok() {
+                    chances.push((*move_, forecast.chances()));
+                }
+            }
+        }
+        Step::Over(outcome) => {
+            match_.record(&replay.game, outcome);
+            forecast = None;
+        }
+    })?;
+
+    Ok(chances)
+}
+
+impl fmt::Display for Chances {
+    /// A win bar: each side's share of the hand, then its chances in
+    /// figures.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const WIDTH: usize = 40;
+        let marks = ['#', '=', '+', '*'];
+
+        let mut bar = String::new();
+        for (side, mark) in self.sides.iter().zip(marks) {
+            let width = (side.hand * WIDTH as f64).round() as usize;
+            bar.push_str(&mark.to_string().repeat(width));
+        }
+        let width = bar.chars().count();
+        bar.push_str(&".".repeat(WIDTH.saturating_sub(width)));
+        write!(f, "[{}]", bar.chars().take(WIDTH).collect::<String>())?;
+
+        for (side, mark) in self.sides.iter().zip(marks) {
+            let seats = side
+                .seats
+                .iter()
+                .map(|seat| seat.to_string())
+                .collect::<Vec<_>>();
+            write!(
+                f,
+                " {} seat {} {:.0}% hand {:.0}% match",
+                mark,
+                seats.join("+"),
+                100.0 * side.hand,
+                100.0 * side.match_
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::heuristics::RandomStrategy;
+    use crate::bots::player::Strategy;
+    use crate::bots::playout::play_match;
+
+    #[test]
+    fn test_chances_follow_the_play() {
+        let rules = Rules::from_options("block target=50").unwrap();
+        let mut strategies = (0..2)
+            .map(|seat| Box::new(RandomStrategy::new(seat)) as Box<dyn Strategy>)
+            .collect::<Vec<_>>();
+        let match_ = play_match(
+            rules.clone(),
+            &mut strategies,
+            &mut StdRng::seed_from_u64(6),
+        );
+
+        let chances = follow(&rules, &match_.log, 50, 1).unwrap();
+        assert!(!chances.is_empty());
+
+        for (_, chances) in &chances {
+            assert_eq!(2, chances.sides.len());
+            let hand = chances.sides.iter().map(|side| side.hand).sum::<f64>();
+            let won = chances.sides.iter().map(|side| side.match_).sum::<f64>();
+            assert!(hand <= 1.0 + 1e-9 && won <= 1.0 + 1e-9);
+        }
+
+        // The last move all but settles the match; only the pips left in
+        // a blocked hand are unseen.
+        let (_, last) = chances.last().unwrap();
+        let winner = match_.winner().unwrap();
+        assert!(last.sides[winner].match_ > 0.9);
+        assert!(last.to_string().starts_with('['));
+    }
+}
@@ -1,46 +1,187 @@
 #![allow(unused)]
 
+use crate::rules::{Misdeal, Rules, Tranque};
 use anyhow::{anyhow, Result};
 use rand::seq::SliceRandom;
 use rand::{thread_rng, Rng};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Domino(pub i32, pub i32);
 
+/// Stands in for a tile whose face a player cannot see.
+pub const HIDDEN: Domino = Domino(-100, -100);
+
 impl Domino {
     pub fn match_right(&self, number: i32) -> Option<Domino> {
-        match self {
-            Domino(x, _) if *x == number => Some(*self),
-            Domino(_, y) if *y == number => Some(self.reverse()),
-            _ => None,
-        }
+        self.match_right_with(number, |half, end| half == end)
     }
 
     pub fn match_left(&self, number: i32) -> Option<Domino> {
+        self.match_left_with(number, |half, end| half == end)
+    }
+
+    /// Orients the tile to follow an end showing `number`, `.0` being the
+    /// half that touches it, when `matches(half, number)` holds for a half.
+    pub fn match_right_with(
+        &self,
+        number: i32,
+        matches: impl Fn(i32, i32) -> bool,
+    ) -> Option<Domino> {
         match self {
-            Domino(x, _) if *x == number => Some(self.reverse()),
-            Domino(_, y) if *y == number => Some(*self),
+            Domino(x, _) if matches(*x, number) => Some(*self),
+            Domino(_, y) if matches(*y, number) => Some(self.reverse()),
             _ => None,
         }
     }
 
+    /// Orients the tile to precede an end showing `number`, `.1` being the
+    /// half that touches it.
+    pub fn match_left_with(
+        &self,
+        number: i32,
+        matches: impl Fn(i32, i32) -> bool,
+    ) -> Option<Domino> {
+        self.match_right_with(number, matches)
+            .map(|domino| domino.reverse())
+    }
+
     pub fn reverse(&self) -> Domino {
         Domino(self.1, self.0)
     }
+
+    pub fn is_double(&self) -> bool {
+        self.0 == self.1
+    }
+
+    pub fn pips(&self) -> i32 {
+        self.0 + self.1
+    }
+
+    /// The tile with its higher half first, as the set is built.
+    pub fn normalized(&self) -> Domino {
+        if self.0 >= self.1 {
+            *self
+        } else {
+            self.reverse()
+        }
+    }
+
+    /// Reads a tile written as `6-4`.
+    pub fn parse(string: &str) -> Option<Domino> {
+        let (left, right) = string.split_once('-')?;
+        Some(Domino(left.parse().ok()?, right.parse().ok()?))
+    }
+}
+
+impl std::fmt::Display for Domino {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}-{}", self.0, self.1)
+    }
+}
+
+/// A line of tiles growing out of a double, apart from the main line.
+/// Tiles are oriented so that `.0` touches the previous tile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch {
+    pub anchor: Domino,
+    pub tiles: Vec<Domino>,
+}
+
+impl Branch {
+    pub fn end(&self) -> i32 {
+        self.tiles.last().map_or(self.anchor.1, |domino| domino.1)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    Left,
+    Right,
+    Branch(usize),
+}
+
+impl End {
+    pub fn to_move(self, player: usize, piece: usize) -> Move {
+        match self {
+            End::Left => Move::Left(player, piece),
+            End::Right => Move::Right(player, piece),
+            End::Branch(branch) => Move::Branch(player, branch, piece),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreKind {
+    /// The open ends add up to a multiple of five.
+    Fives,
+    /// Both ends of the line show the same number.
+    DoubleHeader,
+    /// Both ends show the same number and one of them is a double.
+    TripleHeader,
+}
+
+/// Points scored by a single move, as it happens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Score {
+    pub player: usize,
+    pub kind: ScoreKind,
+    pub points: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Won(usize),
+    Blocked,
+}
+
+/// A tile placed on the board, with the numbers both ends of the line showed
+/// just before it went down.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub player: usize,
+    pub domino: Domino,
+    pub end: End,
+    pub line_ends: Option<(i32, i32)>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Game {
     pub players: [Vec<Domino>; 4],
     pub board: Vec<Domino>,
+    pub branches: Vec<Branch>,
+    pub boneyard: Vec<Domino>,
     pub next: i32,
+    pub rules: Rules,
+    pub last: Option<Placement>,
+    pub spinner: Option<Domino>,
+    /// Points scored during the hand, before the hand itself is scored.
+    pub points: [i32; 4],
+    /// What the last move scored, if anything.
+    pub scored: Option<Score>,
+    /// Tiles drawn while looking for the opening double, in order.
+    pub opening_draws: Vec<(usize, Domino)>,
     n_players: i32,
+    /// The double that has to open the hand, if any.
+    opening: Option<Domino>,
+    /// Ends that still have to be played on before play continues elsewhere.
+    foot: Vec<End>,
+    /// Feet left unfinished when a double landed on them, the latest last.
+    /// Each is taken up again once the foot opened after it is finished.
+    feet: Vec<Vec<End>>,
+    /// Consecutive passes, used to detect a blocked hand.
+    passes: i32,
+    /// Tiles drawn by the player on turn.
+    drawn: usize,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Move {
     Left(usize, usize),
     Right(usize, usize),
+    /// Player, branch and piece position.
+    Branch(usize, usize, usize),
+    Draw(usize),
+    Pass(usize),
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -48,6 +189,8 @@ pub enum Update {
     Skip,
     Left(Domino),
     Right(Domino),
+    Branch(usize, Domino),
+    Draw,
 }
 
 impl Move {
@@ -64,6 +207,14 @@ impl Move {
                     Some(Move::Right(player, pos_))
                 }
             }
+            ["branch", branch, pos] => {
+                let branch_: usize = branch.parse().ok()?;
+                let pos_: usize = pos.parse().ok()?;
+
+                Some(Move::Branch(player, branch_, pos_))
+            }
+            ["draw"] => Some(Move::Draw(player)),
+            ["pass"] => Some(Move::Pass(player)),
             _ => None,
         }
     }
@@ -84,88 +235,729 @@ impl Move {
                 }
             }
 
+            ["branch", player, branch, pos] => {
+                let player_: usize = player.parse().ok()?;
+                let branch_: usize = branch.parse().ok()?;
+                let pos_: usize = pos.parse().ok()?;
+
+                Some(Move::Branch(player_, branch_, pos_))
+            }
+
+            [action @ "draw" | action @ "pass", player] => {
+                let player_: usize = player.parse().ok()?;
+
+                if *action == "draw" {
+                    Some(Move::Draw(player_))
+                } else {
+                    Some(Move::Pass(player_))
+                }
+            }
+
             _ => None,
         }
     }
 
+    pub fn player(&self) -> usize {
+        match *self {
+            Move::Left(player, _) => player,
+            Move::Right(player, _) => player,
+            Move::Branch(player, _, _) => player,
+            Move::Draw(player) => player,
+            Move::Pass(player) => player,
+        }
+    }
+
     fn unpack(&self) -> (usize, usize) {
         match *self {
             Move::Left(x, y) => (x, y),
             Move::Right(x, y) => (x, y),
+            Move::Branch(x, _, y) => (x, y),
+            Move::Draw(x) => (x, 0),
+            Move::Pass(x) => (x, 0),
+        }
+    }
+
+    fn end(&self) -> Option<End> {
+        match *self {
+            Move::Left(..) => Some(End::Left),
+            Move::Right(..) => Some(End::Right),
+            Move::Branch(_, branch, _) => Some(End::Branch(branch)),
+            _ => None,
+        }
+    }
+}
+
+/// Written the way `Move::parse` reads it.
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Move::Left(player, pos) => write!(f, "left {} {}", player, pos),
+            Move::Right(player, pos) => write!(f, "right {} {}", player, pos),
+            Move::Branch(player, branch, pos) => write!(f, "branch {} {} {}", player, branch, pos),
+            Move::Draw(player) => write!(f, "draw {}", player),
+            Move::Pass(player) => write!(f, "pass {}", player),
         }
     }
 }
 
 impl Game {
-    fn shuffled_pieces() -> Vec<Domino> {
-        let mut rng = thread_rng();
-        let mut pieces = (0..7)
+    fn shuffled_pieces(max_pip: i32, rng: &mut impl Rng) -> Vec<Domino> {
+        let mut pieces = (0..=max_pip)
             .flat_map(|i| (0..(i + 1)).map(move |j| Domino(i, j)))
             .collect::<Vec<Domino>>();
-        pieces.shuffle(&mut rng);
+        pieces.shuffle(rng);
         pieces
     }
 
     pub fn new(n_players: i32) -> Game {
-        let pieces = Game::shuffled_pieces();
+        Game::deal(Rules::default(), n_players, 0)
+    }
 
-        let players = match n_players {
-            2 => [
-                pieces[0..14].to_vec(),
-                pieces[14..].to_vec(),
-                vec![],
-                vec![],
-            ],
-            3 => [
-                pieces[0..9].to_vec(),
-                pieces[9..18].to_vec(),
-                pieces[18..27].to_vec(),
-                vec![],
-            ],
-            _ => [
-                pieces[0..7].to_vec(),
-                pieces[7..14].to_vec(),
-                pieces[14..21].to_vec(),
-                pieces[21..].to_vec(),
-            ],
-        };
+    /// Deals the given hand of a match played under `rules`.
+    pub fn deal(rules: Rules, n_players: i32, hand: usize) -> Game {
+        Game::deal_with(rules, n_players, hand, &mut thread_rng())
+    }
+
+    /// Deals with the given source of randomness, so that a seeded one
+    /// gives the same deal every time.
+    pub fn deal_with(rules: Rules, n_players: i32, hand: usize, rng: &mut impl Rng) -> Game {
+        let pieces = Game::shuffled_pieces(rules.max_pip, rng);
+        let n_players = n_players.clamp(2, 4);
+        let size = rules
+            .hand_size(n_players)
+            .min(pieces.len() / n_players as usize);
+
+        let mut players: [Vec<Domino>; 4] = Default::default();
+        for (i, player) in players.iter_mut().take(n_players as usize).enumerate() {
+            *player = pieces[i * size..(i + 1) * size].to_vec();
+        }
+
+        let boneyard = pieces[n_players as usize * size..].to_vec();
+        let opening = rules.opening_double(hand);
+
+        let mut game = Game::empty(rules, n_players);
+        game.players = players;
+        game.boneyard = boneyard;
+        game.opening = opening;
+
+        if let Some(double) = game.opening {
+            game.next = game.find_opener(double) as i32;
+        }
+
+        game
+    }
+
+    /// Sets up a recorded deal again. Tiles held beyond the hand size were
+    /// drawn looking for the opening double, in turn from seat 0.
+    pub fn from_deal(
+        rules: Rules,
+        n_players: i32,
+        hand: usize,
+        players: [Vec<Domino>; 4],
+        boneyard: Vec<Domino>,
+        next: i32,
+    ) -> Game {
+        let n_players = n_players.clamp(2, 4);
+        let size = rules
+            .hand_size(n_players)
+            .min(rules.set_size() / n_players as usize);
+        let drawn = players
+            .iter()
+            .map(|player| player.len().saturating_sub(size))
+            .sum::<usize>();
+
+        let mut game = Game::empty(rules, n_players);
+        game.opening = game.rules.opening_double(hand);
+        game.opening_draws = (0..drawn)
+            .map(|draw| {
+                let seat = draw % n_players as usize;
+                (seat, players[seat][size + draw / n_players as usize])
+            })
+            .collect();
+        game.players = players;
+        game.boneyard = boneyard;
+        game.next = next;
+
+        game
+    }
 
-        let game = Game {
-            players,
+    fn empty(rules: Rules, n_players: i32) -> Game {
+        Game {
+            players: Default::default(),
             board: Vec::new(),
+            branches: Vec::new(),
+            boneyard: Vec::new(),
             next: 0,
+            rules,
+            last: None,
+            spinner: None,
+            points: [0; 4],
+            scored: None,
+            opening_draws: Vec::new(),
             n_players,
-        };
+            opening: None,
+            foot: Vec::new(),
+            feet: Vec::new(),
+            passes: 0,
+            drawn: 0,
+        }
+    }
+
+    /// Seat holding the opening double. When nobody was dealt it, players
+    /// draw in turn until it turns up.
+    fn find_opener(&mut self, double: Domino) -> usize {
+        let n_players = self.n_players as usize;
+
+        if let Some(seat) = (0..n_players).find(|seat| self.players[*seat].contains(&double)) {
+            return seat;
+        }
+
+        let mut seat = 0;
+        while let Some(piece) = self.boneyard.pop() {
+            self.players[seat].push(piece);
+            self.opening_draws.push((seat, piece));
+            if piece == double {
+                return seat;
+            }
+            seat = (seat + 1) % n_players;
+        }
+
+        0
+    }
+
+    /// The hand as `seat` sees it: its own tiles, and hidden tiles in the
+    /// other hands and the boneyard.
+    pub fn hidden(
+        rules: Rules,
+        n_players: i32,
+        hand: usize,
+        seat: usize,
+        tiles: Vec<Domino>,
+    ) -> Game {
+        let n_players = n_players.clamp(2, 4);
+        let set_size = rules.set_size();
+        let size = rules
+            .hand_size(n_players)
+            .min(set_size / n_players as usize);
+        let opening = rules.opening_double(hand);
+
+        let mut game = Game::empty(rules, n_players);
+        for (i, player) in game.players.iter_mut().take(n_players as usize).enumerate() {
+            *player = if i == seat {
+                tiles.clone()
+            } else {
+                vec![HIDDEN; size]
+            };
+        }
+        game.boneyard = vec![HIDDEN; set_size - n_players as usize * size];
+        game.opening = opening;
 
         game
     }
 
+    /// Replays a move seen at the table. Tiles come out of hidden hands as
+    /// they are played; a drawn tile stays hidden.
+    pub fn apply(&mut self, player: usize, update: &Update) -> Result<()> {
+        self.next = player as i32;
+
+        let (piece, in_hand) = match *update {
+            Update::Left(piece) if !self.board.is_empty() => (piece, piece.reverse()),
+            Update::Left(piece) | Update::Right(piece) | Update::Branch(_, piece) => (piece, piece),
+            Update::Draw => {
+                let piece = self
+                    .boneyard
+                    .pop()
+                    .ok_or_else(|| anyhow!("Empty boneyard".to_string()))?;
+                self.players[player].push(piece);
+                self.drawn += 1;
+                self.passes = 0;
+                self.scored = None;
+                return Ok(());
+            }
+            Update::Skip => {
+                self.passes += 1;
+                self.scored = None;
+                self.incr_player();
+                return Ok(());
+            }
+        };
+
+        let hand = &mut self.players[player];
+        let pos = match hand
+            .iter()
+            .position(|domino| *domino == piece || *domino == piece.reverse())
+        {
+            Some(pos) => pos,
+            None => {
+                let pos = hand
+                    .iter()
+                    .position(|domino| *domino == HIDDEN)
+                    .ok_or_else(|| anyhow!("Unknown tile".to_string()))?;
+                hand[pos] = in_hand;
+                pos
+            }
+        };
+
+        let move_ = match *update {
+            Update::Left(_) => Move::Left(player, pos),
+            Update::Right(_) => Move::Right(player, pos),
+            Update::Branch(branch, _) => Move::Branch(player, branch, pos),
+            _ => unreachable!(),
+        };
+
+        self.play(&move_).map(|_| ())
+    }
+
+    pub fn n_players(&self) -> i32 {
+        self.n_players
+    }
+
+    /// Seats that passed in a row since the last tile was placed.
+    pub fn passes(&self) -> i32 {
+        self.passes
+    }
+
     pub fn play(&mut self, move_: &Move) -> Result<Update> {
+        if move_.player() != self.next as usize {
+            return Err(anyhow!("Not your turn".to_string()));
+        }
+
         let update = self.make_move(move_)?;
-        self.incr_player();
+
+        if !matches!(update, Update::Draw) {
+            self.incr_player();
+        }
 
         Ok(update)
     }
 
     fn incr_player(&mut self) {
         self.next += 1;
+        self.drawn = 0;
 
-        if self.next == 4 {
+        if self.next >= self.n_players {
             self.next = 0;
         }
     }
 
+    /// Open ends that can currently be played on, with the number they show.
+    pub fn open_ends(&self) -> Vec<(End, i32)> {
+        if self.board.is_empty() {
+            return Vec::new();
+        }
+
+        if !self.foot.is_empty() {
+            return self
+                .foot
+                .iter()
+                .map(|end| (*end, self.end_value(*end)))
+                .collect();
+        }
+
+        let mut ends = vec![
+            (End::Left, self.end_value(End::Left)),
+            (End::Right, self.end_value(End::Right)),
+        ];
+        ends.extend(
+            self.branches
+                .iter()
+                .enumerate()
+                .map(|(i, branch)| (End::Branch(i), branch.end())),
+        );
+        ends
+    }
+
+    fn end_value(&self, end: End) -> i32 {
+        match end {
+            End::Left => self.board[0].0,
+            End::Right => self.board[self.board.len() - 1].1,
+            End::Branch(i) => self.branches[i].end(),
+        }
+    }
+
+    fn can_open(&self, piece: &Domino) -> bool {
+        self.opening.unwrap_or(*piece) == *piece
+    }
+
+    fn can_draw(&self) -> bool {
+        self.rules.draw
+            && !self.boneyard.is_empty()
+            && self.drawn < self.rules.max_draws.unwrap_or(usize::MAX)
+    }
+
+    /// Every move the given player may make right now. A player that cannot
+    /// place a tile is offered a draw, or a pass once drawing is not possible.
+    pub fn legal_moves(&self, player: usize) -> Vec<Move> {
+        let hand = &self.players[player];
+        let mut moves = Vec::new();
+
+        if self.board.is_empty() {
+            for (pos, piece) in hand.iter().enumerate() {
+                if self.can_open(piece) {
+                    moves.push(Move::Left(player, pos));
+                }
+            }
+        } else {
+            for (end, number) in self.open_ends() {
+                for (pos, piece) in hand.iter().enumerate() {
+                    if self.rules.place(piece, number).is_some() {
+                        moves.push(end.to_move(player, pos));
+                    }
+                }
+            }
+        }
+
+        if moves.is_empty() {
+            if self.can_draw() {
+                moves.push(Move::Draw(player));
+            } else {
+                moves.push(Move::Pass(player));
+            }
+        }
+
+        moves
+    }
+
+    /// The result of the hand, once a player went out or nobody can play.
+    pub fn outcome(&self) -> Option<Outcome> {
+        if self.board.is_empty() {
+            return None;
+        }
+
+        let n_players = self.n_players as usize;
+
+        if let Some(seat) = (0..n_players).find(|seat| self.players[*seat].is_empty()) {
+            return Some(Outcome::Won(seat));
+        }
+
+        if self.passes >= self.n_players {
+            return Some(Outcome::Blocked);
+        }
+
+        None
+    }
+
+    /// First seat whose hand calls for a redeal, with the rule it breaks.
+    pub fn misdeal(&self) -> Option<(usize, Misdeal)> {
+        (0..self.n_players as usize).find_map(|seat| {
+            self.rules
+                .misdeals
+                .iter()
+                .find(|misdeal| misdeal.applies(&self.players[seat]))
+                .map(|misdeal| (seat, *misdeal))
+        })
+    }
+
+    /// Pips left in a seat's hand, as the rules count them.
+    pub fn pips(&self, seat: usize) -> i32 {
+        self.players[seat]
+            .iter()
+            .map(|domino| self.rules.pip_value(domino))
+            .sum()
+    }
+
+    /// The seat taking a blocked hand under the table's tranque rule, or
+    /// `None` when the hand is tied.
+    pub fn blocked_winner(&self) -> Option<usize> {
+        let n_players = self.n_players as usize;
+
+        match self.rules.tranque {
+            Tranque::Blocker => {
+                let blocker = self.last?.player;
+                let next = (blocker + 1) % n_players;
+
+                if self.pips(blocker) <= self.pips(next) {
+                    Some(blocker)
+                } else {
+                    Some(next)
+                }
+            }
+            Tranque::LowestHand => {
+                self.lightest((0..n_players).map(|seat| (seat, self.pips(seat))))
+            }
+            Tranque::LowestTeam => self.lightest((0..n_players).map(|seat| {
+                let pips = (0..n_players)
+                    .filter(|other| self.rules.same_side(n_players, seat, *other))
+                    .map(|other| self.pips(other))
+                    .sum();
+                (seat, pips)
+            })),
+        }
+    }
+
+    /// Lightest seat among those tied for the fewest `(seat, pips)`, as long
+    /// as they all sit on the same side.
+    fn lightest(&self, pips: impl Iterator<Item = (usize, i32)>) -> Option<usize> {
+        let n_players = self.n_players as usize;
+        let pips = pips.collect::<Vec<_>>();
+        let lowest = pips.iter().map(|(_, pips)| *pips).min()?;
+        let seats = pips
+            .iter()
+            .filter(|(_, pips)| *pips == lowest)
+            .map(|(seat, _)| *seat)
+            .collect::<Vec<_>>();
+
+        if seats
+            .iter()
+            .any(|seat| !self.rules.same_side(n_players, seats[0], *seat))
+        {
+            return None;
+        }
+
+        seats.into_iter().min_by_key(|seat| self.pips(*seat))
+    }
+
     fn make_move(&mut self, move_: &Move) -> Result<Update> {
+        self.scored = None;
+
+        match *move_ {
+            Move::Draw(player_num) => return self.draw(player_num),
+            Move::Pass(player_num) => return self.pass(player_num),
+            _ => {}
+        }
+
         if self.board.is_empty() {
             let (player_num, piece_pos) = move_.unpack();
-            let piece = self.players[player_num].remove(piece_pos);
+            let piece = *self.players[player_num]
+                .get(piece_pos)
+                .ok_or_else(|| anyhow!("Invalid move".to_string()))?;
+
+            if !self.can_open(&piece) {
+                return Err(anyhow!("Invalid move".to_string()));
+            }
+
+            self.players[player_num].remove(piece_pos);
             self.board.push(piece);
+            self.placed(Placement {
+                player: player_num,
+                domino: piece,
+                end: End::Left,
+                line_ends: None,
+            });
             return Ok(Update::Left(piece));
         }
 
-        match *move_ {
+        if let Some(end) = move_.end() {
+            if !self.foot.is_empty() && !self.foot.contains(&end) {
+                return Err(anyhow!("Invalid move".to_string()));
+            }
+        }
+
+        let (player_num, piece_pos) = move_.unpack();
+        if piece_pos >= self.players[player_num].len() {
+            return Err(anyhow!("Invalid move".to_string()));
+        }
+
+        let line_ends = (self.end_value(End::Left), self.end_value(End::Right));
+
+        let update = match *move_ {
             Move::Left(player_num, piece_pos) => self.play_left(player_num, piece_pos),
             Move::Right(player_num, piece_pos) => self.play_right(player_num, piece_pos),
+            Move::Branch(player_num, branch, piece_pos) => {
+                self.play_branch(player_num, branch, piece_pos)
+            }
+            _ => unreachable!(),
+        }?;
+
+        let (end, piece) = match update {
+            Update::Left(piece) => (End::Left, piece),
+            Update::Right(piece) => (End::Right, piece),
+            Update::Branch(branch, piece) => (End::Branch(branch), piece),
+            _ => unreachable!(),
+        };
+
+        self.placed(Placement {
+            player: player_num,
+            domino: piece,
+            end,
+            line_ends: Some(line_ends),
+        });
+
+        Ok(update)
+    }
+
+    /// Bookkeeping after a tile went down: feet and spinners the variant
+    /// opens, and points scored on the ends.
+    fn placed(&mut self, placement: Placement) {
+        let first = placement.line_ends.is_none();
+
+        self.passes = 0;
+        self.last = Some(placement);
+        self.foot.retain(|foot_end| *foot_end != placement.end);
+        self.open_foot(placement.end, placement.domino, first);
+
+        if self.foot.is_empty() {
+            self.foot = self.feet.pop().unwrap_or_default();
+        }
+
+        if self.rules.spinner {
+            self.open_spinner(placement);
+        }
+
+        self.scored = self.score_ends(placement.player);
+
+        if let Some(score) = self.scored {
+            self.points[score.player] += score.points;
+        }
+    }
+
+    /// Points the ends are worth right after `player` placed a tile.
+    fn score_ends(&self, player: usize) -> Option<Score> {
+        let kind = if self.rules.headers {
+            self.header()?
+        } else if self.rules.fives {
+            let sum = self.end_sum();
+            if sum == 0 || sum % 5 != 0 {
+                return None;
+            }
+            return Some(Score {
+                player,
+                kind: ScoreKind::Fives,
+                points: sum,
+            });
+        } else {
+            return None;
+        };
+
+        let points = match kind {
+            ScoreKind::TripleHeader => 3,
+            _ => 2,
+        };
+
+        Some(Score {
+            player,
+            kind,
+            points,
+        })
+    }
+
+    /// Both ends of the line showing the same number make a double-header,
+    /// a triple-header when one of them is the double of that number. A
+    /// double played first is a double-header.
+    fn header(&self) -> Option<ScoreKind> {
+        match self.board.as_slice() {
+            [only] if only.is_double() => Some(ScoreKind::DoubleHeader),
+            [first, .., last] if first.0 == last.1 => {
+                if first.is_double() || last.is_double() {
+                    Some(ScoreKind::TripleHeader)
+                } else {
+                    Some(ScoreKind::DoubleHeader)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// The first double on the line becomes the spinner, which grows two
+    /// more branches once both of its sides have been played on.
+    fn open_spinner(&mut self, placement: Placement) {
+        let on_line = matches!(placement.end, End::Left | End::Right);
+
+        if self.spinner.is_none() && on_line && placement.domino.is_double() {
+            self.spinner = Some(placement.domino);
+        }
+
+        let spinner = match self.spinner {
+            Some(spinner) => spinner,
+            None => return,
+        };
+
+        let position = self.board.iter().position(|domino| *domino == spinner);
+        let flanked = matches!(position, Some(i) if i > 0 && i + 1 < self.board.len());
+        let opened = self.branches.iter().any(|branch| branch.anchor == spinner);
+
+        if flanked && !opened {
+            for _ in 0..2 {
+                self.branches.push(Branch {
+                    anchor: spinner,
+                    tiles: Vec::new(),
+                });
+            }
+        }
+    }
+
+    /// Sum of the open ends, a double at an end counting both halves. A lone
+    /// first tile counts all its pips.
+    pub fn end_sum(&self) -> i32 {
+        let end_pips = |domino: &Domino, number: i32| {
+            if domino.is_double() {
+                2 * number
+            } else {
+                number
+            }
+        };
+
+        match self.board.as_slice() {
+            [] => 0,
+            [only] => only.pips(),
+            [first, .., last] => {
+                let branches: i32 = self
+                    .branches
+                    .iter()
+                    .filter_map(|branch| branch.tiles.last())
+                    .map(|domino| end_pips(domino, domino.1))
+                    .sum();
+
+                end_pips(first, first.0) + end_pips(last, last.1) + branches
+            }
+        }
+    }
+
+    /// Once a double lands on `end`, the variant may require a foot of tiles
+    /// on it: the end itself plus new branches hanging off the double.
+    fn open_foot(&mut self, end: End, piece: Domino, first: bool) {
+        let size = self.rules.foot_size(first);
+
+        if !piece.is_double() || size == 0 {
+            return;
+        }
+
+        if !self.foot.is_empty() {
+            self.feet.push(std::mem::take(&mut self.foot));
+        }
+
+        if first {
+            self.foot.push(End::Left);
+            self.foot.push(End::Right);
+        } else {
+            self.foot.push(end);
+        }
+
+        while self.foot.len() < size {
+            self.branches.push(Branch {
+                anchor: piece,
+                tiles: Vec::new(),
+            });
+            self.foot.push(End::Branch(self.branches.len() - 1));
+        }
+    }
+
+    fn draw(&mut self, player_num: usize) -> Result<Update> {
+        let can_place = self
+            .legal_moves(player_num)
+            .iter()
+            .any(|move_| move_.end().is_some());
+
+        if can_place || !self.can_draw() {
+            return Err(anyhow!("Invalid move".to_string()));
+        }
+
+        if let Some(piece) = self.boneyard.pop() {
+            self.players[player_num].push(piece);
+            self.drawn += 1;
+            self.passes = 0;
+        }
+
+        Ok(Update::Draw)
+    }
+
+    fn pass(&mut self, player_num: usize) -> Result<Update> {
+        match self.legal_moves(player_num).as_slice() {
+            [Move::Pass(_)] => {
+                self.passes += 1;
+                Ok(Update::Skip)
+            }
+            _ => Err(anyhow!("Invalid move".to_string())),
         }
     }
 
@@ -173,7 +965,10 @@ impl Game {
         let piece_from_board = self.board[0];
         let piece_to_play = self.players[player_num][piece_pos];
 
-        let piece_to_play = piece_to_play.match_left(piece_from_board.0);
+        let piece_to_play = self
+            .rules
+            .place(&piece_to_play, piece_from_board.0)
+            .map(|domino| domino.reverse());
 
         if let Some(piece) = piece_to_play {
             self.board.insert(0, piece);
@@ -188,7 +983,7 @@ impl Game {
         let piece_from_board = self.board[self.board.len() - 1];
         let piece_to_play = self.players[player_num][piece_pos];
 
-        let piece_to_play = piece_to_play.match_right(piece_from_board.1);
+        let piece_to_play = self.rules.place(&piece_to_play, piece_from_board.1);
 
         if let Some(piece) = piece_to_play {
             self.board.push(piece);
@@ -198,6 +993,30 @@ impl Game {
 
         Err(anyhow!("Invalid move".to_string()))
     }
+
+    fn play_branch(
+        &mut self,
+        player_num: usize,
+        branch: usize,
+        piece_pos: usize,
+    ) -> Result<Update> {
+        let end = self
+            .branches
+            .get(branch)
+            .ok_or_else(|| anyhow!("Invalid move".to_string()))?
+            .end();
+        let piece_to_play = self.players[player_num][piece_pos];
+
+        let piece_to_play = self.rules.place(&piece_to_play, end);
+
+        if let Some(piece) = piece_to_play {
+            self.branches[branch].tiles.push(piece);
+            self.players[player_num].remove(piece_pos);
+            return Ok(Update::Branch(branch, piece));
+        }
+
+        Err(anyhow!("Invalid move".to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -232,6 +1051,7 @@ mod tests {
             board: vec![Domino(3, 4), Domino(4, 2)],
             next: 0,
             n_players: 4,
+            ..Game::empty(Rules::default(), 4)
         };
 
         let result = game.make_move(&Move::Left(0, 2));
@@ -250,6 +1070,13 @@ mod tests {
             board: vec![Domino(1, 3), Domino(3, 4), Domino(4, 2)],
             next: 0,
             n_players: 4,
+            last: Some(Placement {
+                player: 0,
+                domino: Domino(1, 3),
+                end: End::Left,
+                line_ends: Some((3, 2)),
+            }),
+            ..Game::empty(Rules::default(), 4)
         };
 
         assert_eq!(expected, game);
@@ -267,6 +1094,7 @@ mod tests {
             board: vec![Domino(3, 4), Domino(4, 2)],
             next: 0,
             n_players: 4,
+            ..Game::empty(Rules::default(), 4)
         };
 
         let result = game.make_move(&Move::Right(0, 0));
@@ -285,6 +1113,13 @@ mod tests {
             board: vec![Domino(3, 4), Domino(4, 2), Domino(2, 1)],
             next: 0,
             n_players: 4,
+            last: Some(Placement {
+                player: 0,
+                domino: Domino(2, 1),
+                end: End::Right,
+                line_ends: Some((3, 2)),
+            }),
+            ..Game::empty(Rules::default(), 4)
         };
 
         assert_eq!(expected, game);
@@ -302,6 +1137,7 @@ mod tests {
             board: vec![Domino(3, 4), Domino(4, 2)],
             next: 0,
             n_players: 4,
+            ..Game::empty(Rules::default(), 4)
         };
 
         let moves = [
@@ -332,6 +1168,13 @@ mod tests {
             ],
             next: 0,
             n_players: 4,
+            last: Some(Placement {
+                player: 0,
+                domino: Domino(6, 4),
+                end: End::Left,
+                line_ends: Some((4, 5)),
+            }),
+            ..Game::empty(Rules::default(), 4)
         };
 
         assert_eq!(expected, game);
@@ -349,6 +1192,7 @@ mod tests {
             board: Vec::new(),
             next: 0,
             n_players: 4,
+            ..Game::empty(Rules::default(), 4)
         };
 
         let expected = Game {
@@ -361,6 +1205,13 @@ mod tests {
             board: vec![Domino(2, 2)],
             next: 0,
             n_players: 4,
+            last: Some(Placement {
+                player: 0,
+                domino: Domino(2, 2),
+                end: End::Left,
+                line_ends: None,
+            }),
+            ..Game::empty(Rules::default(), 4)
         };
 
         let result = game.make_move(&Move::Left(0, 1));
@@ -389,4 +1240,309 @@ mod tests {
             assert_eq!(result, Move::parse(string).unwrap());
         }
     }
+
+    #[test]
+    fn parse_branch_draw_and_pass() {
+        assert_eq!(Some(Move::Branch(2, 3, 1)), Move::parse("branch 2 3 1"));
+        assert_eq!(
+            Some(Move::Branch(1, 0, 4)),
+            Move::parse_move("branch 0 4", 1)
+        );
+        assert_eq!(Some(Move::Draw(3)), Move::parse_move("draw", 3));
+        assert_eq!(Some(Move::Pass(0)), Move::parse("pass 0"));
+    }
+
+    #[test]
+    fn test_deal_sizes() {
+        let game = Game::deal(Rules::chicken_foot(), 4, 0);
+        let dealt: usize = game.players.iter().map(|hand| hand.len()).sum();
+
+        assert_eq!(55, dealt + game.boneyard.len());
+        assert!(game.players[game.next as usize].contains(&Domino(9, 9)));
+
+        let game = Game::new(3);
+        assert_eq!(9, game.players[0].len());
+        assert_eq!(1, game.boneyard.len());
+    }
+
+    #[test]
+    fn test_chicken_foot() {
+        let mut game = Game {
+            players: [
+                vec![Domino(9, 9), Domino(9, 1), Domino(2, 2)],
+                vec![Domino(9, 2), Domino(2, 3), Domino(9, 4)],
+                vec![],
+                vec![],
+            ],
+            board: Vec::new(),
+            next: 0,
+            n_players: 2,
+            opening: Some(Domino(9, 9)),
+            ..Game::empty(Rules::chicken_foot(), 2)
+        };
+
+        assert!(game.play(&Move::Left(0, 1)).is_err());
+        assert!(game.play(&Move::Left(0, 0)).is_ok());
+        assert_eq!(4, game.branches.len());
+        assert_eq!(6, game.open_ends().len());
+
+        game.play(&Move::Branch(1, 2, 0)).unwrap();
+        game.play(&Move::Right(0, 0)).unwrap();
+
+        // nothing fits the remaining toes of 9-9, and 2-3 may not go on 9-2
+        assert!(game.play(&Move::Branch(1, 2, 0)).is_err());
+        game.play(&Move::Left(1, 1)).unwrap();
+
+        assert_eq!(3, game.open_ends().len());
+        assert_eq!(vec![Move::Pass(0)], game.legal_moves(0));
+    }
+
+    #[test]
+    fn test_chicken_foot_on_a_foot() {
+        let mut game = Game {
+            players: [
+                vec![Domino(6, 6), Domino(6, 2), Domino(5, 5), Domino(0, 0)],
+                vec![Domino(6, 5), Domino(3, 1), Domino(6, 1), Domino(0, 1)],
+                vec![],
+                vec![],
+            ],
+            board: vec![Domino(3, 4), Domino(4, 6)],
+            next: 0,
+            n_players: 2,
+            ..Game::empty(Rules::chicken_foot(), 2)
+        };
+
+        game.play(&Move::Right(0, 0)).unwrap();
+        assert_eq!(3, game.open_ends().len());
+        assert!(game.play(&Move::Left(1, 1)).is_err());
+        game.play(&Move::Branch(1, 0, 0)).unwrap();
+
+        // 5-5 fits the toe just played on, but 6-6 has to be footed first
+        assert!(game.play(&Move::Branch(0, 0, 1)).is_err());
+        game.play(&Move::Right(0, 0)).unwrap();
+        game.play(&Move::Branch(1, 1, 1)).unwrap();
+        game.play(&Move::Branch(0, 0, 0)).unwrap();
+
+        let ends = game.open_ends();
+        assert_eq!(3, ends.len());
+        assert!(ends.iter().all(|(_, value)| *value == 5));
+    }
+
+    #[test]
+    fn test_chicken_foot_on_a_foot_sum_to() {
+        // only when touching ends add up can a double land on a foot
+        let mut game = Game {
+            players: [
+                vec![Domino(9, 9), Domino(9, 1), Domino(9, 2)],
+                vec![Domino(3, 3), Domino(9, 5)],
+                vec![],
+                vec![],
+            ],
+            board: Vec::new(),
+            next: 0,
+            n_players: 2,
+            opening: Some(Domino(9, 9)),
+            ..Game::empty(Rules::from_options("chicken-foot sum-to=12").unwrap(), 2)
+        };
+
+        game.play(&Move::Left(0, 0)).unwrap();
+        // 3-3 lands on a toe of 9-9 and has to be footed first
+        game.play(&Move::Branch(1, 0, 0)).unwrap();
+        assert_eq!(3, game.open_ends().len());
+        assert!(game.play(&Move::Branch(0, 1, 0)).is_err());
+
+        game.play(&Move::Branch(0, 4, 0)).unwrap();
+        game.play(&Move::Branch(1, 5, 0)).unwrap();
+        game.play(&Move::Branch(0, 0, 0)).unwrap();
+
+        // then the toes of 9-9 left empty
+        let ends = game.open_ends();
+        assert_eq!(5, ends.len());
+        assert!(ends
+            .iter()
+            .all(|(end, value)| *value == 9 && *end != End::Branch(0)));
+    }
+
+    #[test]
+    fn test_blocked() {
+        let mut game = Game {
+            players: [vec![Domino(1, 1)], vec![Domino(2, 2)], vec![], vec![]],
+            board: vec![Domino(3, 4)],
+            next: 0,
+            n_players: 2,
+            ..Game::empty(Rules::default(), 2)
+        };
+
+        assert!(game.play(&Move::Right(0, 0)).is_err());
+        game.play(&Move::Pass(0)).unwrap();
+        assert_eq!(None, game.outcome());
+        game.play(&Move::Pass(1)).unwrap();
+        assert_eq!(Some(Outcome::Blocked), game.outcome());
+    }
+
+    #[test]
+    fn test_caribbean_deal() {
+        let game = Game::deal(Rules::caribbean(), 4, 0);
+
+        assert!(game.players.iter().all(|hand| hand.len() == 10));
+        assert_eq!(15, game.boneyard.len());
+        assert!(!game.rules.draw);
+    }
+
+    #[test]
+    fn test_tranque() {
+        let mut game = Game {
+            players: [
+                vec![Domino(6, 5)],
+                vec![Domino(1, 0)],
+                vec![Domino(0, 0)],
+                vec![Domino(4, 4)],
+            ],
+            board: vec![Domino(9, 9)],
+            last: Some(Placement {
+                player: 0,
+                domino: Domino(9, 9),
+                end: End::Left,
+                line_ends: Some((9, 8)),
+            }),
+            n_players: 4,
+            ..Game::empty(Rules::caribbean(), 4)
+        };
+
+        game.rules.tranque = Tranque::LowestHand;
+        assert_eq!(Some(2), game.blocked_winner());
+
+        game.rules.tranque = Tranque::LowestTeam;
+        assert_eq!(Some(1), game.blocked_winner());
+
+        game.rules.tranque = Tranque::Blocker;
+        assert_eq!(Some(1), game.blocked_winner());
+    }
+
+    #[test]
+    fn test_sniff_spinner() {
+        let mut game = Game {
+            players: [
+                vec![Domino(5, 5), Domino(5, 0), Domino(6, 6)],
+                vec![Domino(5, 2), Domino(5, 4), Domino(1, 1)],
+                vec![],
+                vec![],
+            ],
+            boneyard: vec![Domino(3, 3)],
+            n_players: 2,
+            ..Game::empty(Rules::sniff(), 2)
+        };
+
+        game.play(&Move::Left(0, 0)).unwrap();
+        assert_eq!(Some(Domino(5, 5)), game.spinner);
+        assert_eq!([10, 0, 0, 0], game.points);
+
+        game.play(&Move::Right(1, 0)).unwrap();
+        assert!(game.branches.is_empty());
+        assert_eq!(12, game.end_sum());
+
+        game.play(&Move::Left(0, 0)).unwrap();
+        assert_eq!(2, game.branches.len());
+        assert_eq!([10, 0, 0, 0], game.points);
+
+        game.play(&Move::Branch(1, 0, 0)).unwrap();
+        assert_eq!(6, game.end_sum());
+        assert_eq!(vec![Move::Draw(0)], game.legal_moves(0));
+    }
+
+    #[test]
+    fn test_playouts_finish() {
+        let variants = [
+            Rules::block(),
+            Rules::chicken_foot(),
+            Rules::brazilian(),
+            Rules::caribbean(),
+            Rules::sniff(),
+            Rules::matador(),
+            Rules::bergen(),
+        ];
+
+        for rules in variants {
+            for n_players in 2..=4 {
+                let mut game = Game::deal(rules.clone(), n_players, 0);
+                let tiles = rules.set_size();
+                let mut turns = 0;
+
+                while game.outcome().is_none() {
+                    let moves = game.legal_moves(game.next as usize);
+                    let move_ = moves[turns % moves.len()];
+                    game.play(&move_).unwrap();
+                    turns += 1;
+                    assert!(turns < 1000);
+                }
+
+                let on_board =
+                    game.board.len() + game.branches.iter().map(|b| b.tiles.len()).sum::<usize>();
+                let in_hands: usize = game.players.iter().map(|hand| hand.len()).sum();
+                assert_eq!(tiles, on_board + in_hands + game.boneyard.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_matador() {
+        let mut game = Game {
+            players: [
+                vec![Domino(2, 4), Domino(0, 1), Domino(4, 3)],
+                vec![Domino(1, 1), Domino(5, 2)],
+                vec![],
+                vec![],
+            ],
+            board: vec![Domino(0, 6)],
+            n_players: 2,
+            ..Game::empty(Rules::matador(), 2)
+        };
+
+        // only a matador goes on the blank, 1 + 6 makes seven
+        assert_eq!(
+            vec![Move::Left(0, 2), Move::Right(0, 1), Move::Right(0, 2)],
+            game.legal_moves(0)
+        );
+
+        game.play(&Move::Right(0, 1)).unwrap();
+        assert_eq!(vec![Domino(0, 6), Domino(1, 0)], game.board);
+
+        assert!(game.play(&Move::Right(1, 0)).is_err());
+        game.play(&Move::Right(1, 1)).unwrap();
+        assert_eq!(vec![Domino(0, 6), Domino(1, 0), Domino(5, 2)], game.board);
+    }
+
+    #[test]
+    fn test_bergen_headers() {
+        let mut game = Game {
+            players: [
+                vec![Domino(3, 3), Domino(1, 3)],
+                vec![Domino(3, 1), Domino(1, 1)],
+                vec![],
+                vec![],
+            ],
+            n_players: 2,
+            ..Game::empty(Rules::bergen(), 2)
+        };
+
+        game.play(&Move::Left(0, 0)).unwrap();
+        assert_eq!(ScoreKind::DoubleHeader, game.scored.unwrap().kind);
+
+        game.play(&Move::Right(1, 0)).unwrap();
+        assert_eq!(None, game.scored);
+
+        game.play(&Move::Left(0, 0)).unwrap();
+        assert_eq!(ScoreKind::DoubleHeader, game.scored.unwrap().kind);
+
+        game.play(&Move::Right(1, 0)).unwrap();
+        assert_eq!(
+            Some(Score {
+                player: 1,
+                kind: ScoreKind::TripleHeader,
+                points: 3
+            }),
+            game.scored
+        );
+        assert_eq!([4, 3, 0, 0], game.points);
+    }
 }
@@ -5,11 +5,32 @@ use crate::game::Move;use crate::grpc::converters;
 use crate::grpc::server::dominoes;
 use crate::grpc::server::dominoes::game_service_client::*;
 
-const SKIP_MSG: dominoes::Message = dominoes::Message {
-    content: Some(dominoes::message::Content::Skip(dominoes::Skip {})),
-};
+fn join_msg(game_id: &str, options: &str) -> dominoes::Message {
+    dominoes::Message {
+        content: Some(dominoes::message::Content::Join(dominoes::Join {
+            game_id: game_id.to_string(),
+            options: options.to_string(),
+        })),
+    }
+}
+
+fn hint_request_msg() -> dominoes::Message {
+    dominoes::Message {
+        content: Some(dominoes::message::Content::HintRequest(
+            dominoes::HintRequest {},
+        )),
+    }
+}
+
+fn coach_msg(on: bool) -> dominoes::Message {
+    dominoes::Message {
+        content: Some(dominoes::message::Content::Coach(dominoes::Coach { on })),
+    }
+}
 
-fn read_move(num_player: i32) -> dominoes::Message {
+/// Reads a move, `hint`, or `coach on`/`coach off`. The flag tells whether
+/// the server answers the message.
+fn read_move(num_player: i32) -> (dominoes::Message, bool) {
     use std::io::{stdin, stdout, Write};    
 
     loop {
@@ -29,19 +50,32 @@ fn read_move(num_player: i32) -> dominoes::Message {
             s.pop();
         }
 
+        match s.trim() {
+            "hint" => return (hint_request_msg(), true),
+            "coach on" => return (coach_msg(true), false),
+            "coach off" => return (coach_msg(false), false),
+            _ => {}
+        }
+
         let move_opt = Move::parse_move(&s, num_player as usize);
 
         if let Some(move_) = move_opt {
-            return converters::move_to_proto(&move_);
+            return (converters::move_to_proto(&move_), true);
         }
     }
 }
 
-async fn requests_stream() -> (Sender<dominoes::Message>, impl Stream<Item = dominoes::Message>) {
+async fn requests_stream(
+    game_id: &str,
+    options: &str,
+) -> (
+    Sender<dominoes::Message>,
+    impl Stream<Item = dominoes::Message>,
+) {
     let (out_sender, out_rec) = mpsc::channel(128);
     
     let stream = ReceiverStream::new(out_rec);
-    let _ = out_sender.send(SKIP_MSG).await;
+    let _ = out_sender.send(join_msg(game_id, options)).await;
     (out_sender, stream)
 }
 
@@ -56,21 +90,76 @@ async fn start_game_loop(sender: Sender<dominoes::Message>, mut stream: tonic::S
         let msg_opt = stream.next().await;
 
         if let Some(Ok(message)) = msg_opt {
-            if let Some(dominoes::message::Content::YouTurn(_)) = message.content {
-                let move_ = read_move(player_number);
-                let _ = sender.send(move_).await;
+            if let Some(dominoes::message::Content::Hints(hints)) = &message.content {
+                println!("Dicas: {:?}", hints.hints);
+            }
+
+            if let Some(dominoes::message::Content::Blunder(blunder)) = &message.content {
+                println!(
+                    "Cuidado, a melhor jogada é {:?}; repita a jogada para confirmar",
+                    blunder.best
+                );
+            }
+
+            if let Some(dominoes::message::Content::YouTurn(turn)) = &message.content {
+                if turn.move_time > 0 {
+                    println!("Tempo para jogar: {:.1}s", turn.move_time as f64 / 1000.0);
+                }
+            }
+
+            if let Some(
+                dominoes::message::Content::YouTurn(_)
+                | dominoes::message::Content::Hints(_)
+                | dominoes::message::Content::Blunder(_),
+            ) = message.content
+            {
+                loop {
+                    let (move_, answered) = read_move(player_number);
+                    let _ = sender.send(move_).await;
+
+                    if answered {
+                        break;
+                    }
+                }
             }
             
-            if let Some(dominoes::message::Content::Update(update)) = message.content { 
+            if let Some(dominoes::message::Content::Init(init)) = &message.content {
+                println!("Sua mão: {:?}", init.hand);
+            }
+
+            if let Some(dominoes::message::Content::Update(update)) = &message.content { 
                 println!("Update: {:?}", update);
             }
+
+            if let Some(dominoes::message::Content::Draw(draw)) = &message.content {
+                println!("Draw: {:?}", draw);
+            }
+
+            if let Some(dominoes::message::Content::Misdeal(misdeal)) = &message.content {
+                println!("Misdeal: {:?}", misdeal);
+            }
+
+            if let Some(dominoes::message::Content::Forfeit(forfeit)) = &message.content {
+                println!("O jogador {} perdeu a partida por tempo", forfeit.seat);
+                return Ok(());
+            }
+
+            if let Some(dominoes::message::Content::HandOver(hand_over)) = &message.content {
+                println!("Placar: {:?}", hand_over.scores);
+
+                if hand_over.finished {
+                    return Ok(());
+                }
+            }
         }        
     }
 }
 
-pub async fn start_client() -> Result<()> {
+/// Joins the table with the given id, or any table waiting for players
+/// when it is empty. Options open a table of the player's own.
+pub async fn start_client(game_id: &str, options: &str) -> Result<()> {
     let mut client = GameServiceClient::connect("http://[::1]:50051").await?;
-    let (sender, stream) = requests_stream().await;
+    let (sender, stream) = requests_stream(game_id, options).await;
     let response = client.join_game(stream).await?;
     let mut resp_stream = response.into_inner();
     let init_opt = resp_stream.next().await;
@@ -1,19 +1,25 @@
 use crate::concurrent;
+use crate::concurrent::Request;
 use crate::game;
 use crate::grpc::server::dominoes;
 use crate::grpc::server::dominoes::message::Content;
+use crate::hints::Hint;
+use crate::scoring::HandResult;
+use std::time::Duration;
 
 const LEFT: i32 = 0;
 const RIGHT: i32 = 1;
+const BRANCH: i32 = 2;
 
-fn to_init_msg(pieces: &Vec<game::Domino>, number: usize) -> dominoes::Message {
-    let hand = pieces
-        .iter()
-        .map(|domino| dominoes::Piece {
-            up: domino.0,
-            down: domino.1,
-        })
-        .collect();
+fn to_piece(domino: &game::Domino) -> dominoes::Piece {
+    dominoes::Piece {
+        up: domino.0,
+        down: domino.1,
+    }
+}
+
+fn to_init_msg(pieces: &[game::Domino], number: usize) -> dominoes::Message {
+    let hand = pieces.iter().map(to_piece).collect();
 
     dominoes::Message {
         content: Some(Content::Init(dominoes::Init {
@@ -23,15 +29,50 @@ fn to_init_msg(pieces: &Vec<game::Domino>, number: usize) -> dominoes::Message {
     }
 }
 
-fn to_update_msg(domino: &game::Domino, position: i32) -> dominoes::Message {
+fn to_score(score: &game::Score) -> dominoes::Score {
+    let kind = match score.kind {
+        game::ScoreKind::Fives => "fives",
+        game::ScoreKind::DoubleHeader => "double-header",
+        game::ScoreKind::TripleHeader => "triple-header",
+    };
+
+    dominoes::Score {
+        player: score.player as i32,
+        kind: kind.to_string(),
+        points: score.points,
+    }
+}
+
+fn to_update_msg(
+    player: usize,
+    domino: &game::Domino,
+    position: i32,
+    branch: usize,
+    score: &Option<game::Score>,
+) -> dominoes::Message {
     dominoes::Message {
         content: Some(Content::Update(dominoes::Update {
-            piece: Some(dominoes::Piece {
-                up: domino.0,
-                down: domino.1,
-            }),
+            piece: Some(to_piece(domino)),
             position,
-            turn: 0,
+            turn: player as i32,
+            branch: branch as i32,
+            score: score.as_ref().map(to_score),
+        })),
+    }
+}
+
+fn to_hand_over_msg(result: &HandResult) -> dominoes::Message {
+    let winner = match result.outcome {
+        game::Outcome::Won(seat) => seat as i32,
+        game::Outcome::Blocked => -1,
+    };
+
+    dominoes::Message {
+        content: Some(Content::HandOver(dominoes::HandOver {
+            winner,
+            points: result.points.to_vec(),
+            scores: result.totals.to_vec(),
+            finished: result.finished,
         })),
     }
 }
@@ -40,49 +81,156 @@ pub fn to_proto(msg: &concurrent::Message) -> dominoes::Message {
     match *msg {
         concurrent::Message::Init((pieces, number)) => to_init_msg(pieces, number),
 
-        concurrent::Message::YourTurn => dominoes::Message {
-            content: Some(Content::YouTurn(dominoes::YourTurn {})),
+        concurrent::Message::YourTurn(time_left) => dominoes::Message {
+            content: Some(Content::YouTurn(dominoes::YourTurn {
+                move_time: time_left.map_or(0, |time_left| millis(time_left.move_)),
+                clock: time_left
+                    .and_then(|time_left| time_left.clock)
+                    .map_or(0, millis),
+            })),
         },
 
-        concurrent::Message::Update(game::Update::Left(domino)) => to_update_msg(&domino, LEFT),
+        concurrent::Message::Update((player, game::Update::Left(domino), score)) => {
+            to_update_msg(player, &domino, LEFT, 0, &score)
+        }
 
-        concurrent::Message::Update(game::Update::Right(domino)) => to_update_msg(&domino, RIGHT),
+        concurrent::Message::Update((player, game::Update::Right(domino), score)) => {
+            to_update_msg(player, &domino, RIGHT, 0, &score)
+        }
 
-        concurrent::Message::Update(game::Update::Skip) => dominoes::Message {
-            content: Some(Content::Skip(dominoes::Skip {})),
+        concurrent::Message::Update((player, game::Update::Branch(branch, domino), score)) => {
+            to_update_msg(player, &domino, BRANCH, branch, &score)
+        }
+
+        concurrent::Message::Update((player, game::Update::Skip, _)) => dominoes::Message {
+            content: Some(Content::Skip(dominoes::Skip {
+                player: player as i32,
+            })),
+        },
+
+        concurrent::Message::Update((player, game::Update::Draw, _)) => dominoes::Message {
+            content: Some(Content::Draw(dominoes::Draw {
+                piece: None,
+                player: player as i32,
+            })),
+        },
+
+        concurrent::Message::Drawn(domino) => dominoes::Message {
+            content: Some(Content::Draw(dominoes::Draw {
+                piece: Some(to_piece(&domino)),
+                ..Default::default()
+            })),
+        },
+
+        concurrent::Message::HandOver(result) => to_hand_over_msg(&result),
+
+        concurrent::Message::Misdeal((seat, misdeal)) => dominoes::Message {
+            content: Some(Content::Misdeal(dominoes::Misdeal {
+                seat: seat as i32,
+                reason: misdeal.to_string(),
+            })),
+        },
+
+        concurrent::Message::Hints(hints) => dominoes::Message {
+            content: Some(Content::Hints(dominoes::Hints {
+                hints: hints.iter().map(to_hint).collect(),
+            })),
+        },
+
+        concurrent::Message::Blunder((best, chosen)) => dominoes::Message {
+            content: Some(Content::Blunder(dominoes::Blunder {
+                best: Some(to_hint(&best)),
+                chosen: Some(to_hint(&chosen)),
+            })),
+        },
+
+        concurrent::Message::Forfeit(seat) => dominoes::Message {
+            content: Some(Content::Forfeit(dominoes::Forfeit { seat: seat as i32 })),
         },
     }
 }
 
+/// Milliseconds, at least one so that a timed turn never reads as untimed.
+fn millis(duration: Duration) -> i32 {
+    duration.as_millis().clamp(1, i32::MAX as u128) as i32
+}
+
+fn to_hint(hint: &Hint) -> dominoes::Hint {
+    let play = match move_to_proto(&hint.move_).content {
+        Some(Content::Move(move_)) => Some(move_),
+        _ => None,
+    };
+
+    dominoes::Hint {
+        play,
+        value: hint.value,
+        draw: matches!(hint.move_, game::Move::Draw(_)),
+        pass: matches!(hint.move_, game::Move::Pass(_)),
+    }
+}
+
 pub fn move_to_proto(move_: &game::Move) -> dominoes::Message {
-    let (side, player_number , piece_position) = match move_ {
-        game::Move::Left(player, piece) => (LEFT, *player as i32, *piece as i32),
-        game::Move::Right(player, piece) => (RIGHT, *player as i32, *piece as i32),
+    let (side, player_number, piece_position, branch) = match move_ {
+        game::Move::Left(player, piece) => (LEFT, *player as i32, *piece as i32, 0),
+        game::Move::Right(player, piece) => (RIGHT, *player as i32, *piece as i32, 0),
+        game::Move::Branch(player, branch, piece) => {
+            (BRANCH, *player as i32, *piece as i32, *branch as i32)
+        }
+        game::Move::Draw(player) => {
+            return dominoes::Message {
+                content: Some(Content::Draw(dominoes::Draw {
+                    piece: None,
+                    player: *player as i32,
+                })),
+            }
+        }
+        game::Move::Pass(player) => {
+            return dominoes::Message {
+                content: Some(Content::Skip(dominoes::Skip {
+                    player: *player as i32,
+                })),
+            }
+        }
     };
 
     dominoes::Message {
-        content: Some(Content::Move(
-            dominoes::Move{
-                side,
-                piece_position,
-                player_number
-            }
-        )),
+        content: Some(Content::Move(dominoes::Move {
+            side,
+            piece_position,
+            player_number,
+            branch,
+        })),
     }
 }
 
-pub fn to_move(msg: &dominoes::Message) -> Option<game::Move> {
-    if let Some(Content::Move(move_)) = &msg.content {
-        let player_pos = move_.player_number as usize;
-        let piece_num = move_.piece_position as usize;
+/// Reads a move sent by the player sitting at seat `player`.
+pub fn to_move(msg: &dominoes::Message, player: usize) -> Option<game::Move> {
+    match &msg.content {
+        Some(Content::Move(move_)) => {
+            let piece_num = move_.piece_position as usize;
 
-        return match move_.side {
-            LEFT => Some(game::Move::Left(player_pos, piece_num)),
-            _ => Some(game::Move::Right(player_pos, piece_num)),
-        };
+            match move_.side {
+                LEFT => Some(game::Move::Left(player, piece_num)),
+                BRANCH => Some(game::Move::Branch(player, move_.branch as usize, piece_num)),
+                _ => Some(game::Move::Right(player, piece_num)),
+            }
+        }
+
+        Some(Content::Draw(_)) => Some(game::Move::Draw(player)),
+
+        Some(Content::Skip(_)) => Some(game::Move::Pass(player)),
+
+        _ => None,
     }
+}
 
-    None
+/// Reads a request sent by the player sitting at seat `player`.
+pub fn to_request(msg: &dominoes::Message, player: usize) -> Option<Request> {
+    match &msg.content {
+        Some(Content::HintRequest(_)) => Some(Request::Hint),
+        Some(Content::Coach(coach)) => Some(Request::Coach(coach.on)),
+        _ => to_move(msg, player).map(Request::Move),
+    }
 }
 
 #[cfg(test)]
@@ -91,10 +239,75 @@ mod tests {
 
     #[test]
     fn test_converter() { // to be finished, I'm in a hurry now
-        let x = concurrent::Message::Update(game::Update::Left(game::Domino(5, 5)));
+        let x = concurrent::Message::Update((0, game::Update::Left(game::Domino(5, 5)), None));
 
         let y = to_proto(&x);
 
         println!("|{:?}|", y);
     }
+
+    #[test]
+    fn test_update_carries_score() {
+        let score = game::Score {
+            player: 1,
+            kind: game::ScoreKind::TripleHeader,
+            points: 3,
+        };
+        let update = game::Update::Right(game::Domino(4, 4));
+
+        let msg = to_proto(&concurrent::Message::Update((1, update, Some(score))));
+
+        match msg.content {
+            Some(Content::Update(update)) => {
+                assert_eq!(1, update.turn);
+                assert_eq!(Some(3), update.score.map(|score| score.points));
+            }
+            _ => panic!("expected an update"),
+        }
+    }
+
+    #[test]
+    fn test_hints_and_requests() {
+        let hints = [
+            Hint {
+                move_: game::Move::Left(1, 2),
+                value: 3.5,
+            },
+            Hint {
+                move_: game::Move::Pass(1),
+                value: -1.0,
+            },
+        ];
+
+        match to_proto(&concurrent::Message::Hints(&hints)).content {
+            Some(Content::Hints(msg)) => {
+                assert_eq!(
+                    Some(2),
+                    msg.hints[0].play.as_ref().map(|play| play.piece_position)
+                );
+                assert!(msg.hints[1].pass);
+            }
+            _ => panic!("expected hints"),
+        }
+
+        let coach = dominoes::Message {
+            content: Some(Content::Coach(dominoes::Coach { on: true })),
+        };
+        assert_eq!(Some(Request::Coach(true)), to_request(&coach, 1));
+        assert_eq!(
+            Some(Request::Move(game::Move::Draw(1))),
+            to_request(&move_to_proto(&game::Move::Draw(1)), 1)
+        );
+    }
+
+    #[test]
+    fn test_branch_move_round_trip() {
+        let move_ = game::Move::Branch(2, 5, 1);
+
+        assert_eq!(Some(move_), to_move(&move_to_proto(&move_), 2));
+        assert_eq!(
+            Some(game::Move::Pass(3)),
+            to_move(&move_to_proto(&game::Move::Pass(3)), 3)
+        );
+    }
 }
@@ -1,7 +1,8 @@
 use crate::concurrent;
-use crate::concurrent::{start_game, RemotePlayer};
+use crate::concurrent::{RemotePlayer, Request};
 use crate::game;
 use crate::grpc::converters::*;
+use crate::lobby::GameManager;
 use async_trait::async_trait;
 use dominoes::game_service_server::*;
 use dominoes::*;
@@ -19,7 +20,7 @@ type MessageStream = Pin<Box<dyn Stream<Item = Result<Message, Status>> + Send>>
 
 #[derive(Debug)]
 pub struct GrpcServer {
-    game_handler: mpsc::Sender<Box<dyn RemotePlayer>>,
+    manager: GameManager,
 }
 
 struct GrpcPlayer {
@@ -39,10 +40,30 @@ impl RemotePlayer for GrpcPlayer {
         loop {
             let result = self.receiver.recv().await;
 
-            if let Some(Ok(msg_proto)) = result {
-                if let Some(move_) = to_move(&msg_proto) {
-                    return move_;
+            match result {
+                Some(Ok(msg_proto)) => {
+                    if let Some(move_) = to_move(&msg_proto, self.number) {
+                        return move_;
+                    }
                 }
+                Some(Err(_)) => {}
+                None => return game::Move::Pass(self.number),
+            }
+        }
+    }
+
+    async fn read_request(&mut self) -> Request {
+        loop {
+            let result = self.receiver.recv().await;
+
+            match result {
+                Some(Ok(msg_proto)) => {
+                    if let Some(request) = to_request(&msg_proto, self.number) {
+                        return request;
+                    }
+                }
+                Some(Err(_)) => {}
+                None => return Request::Leave,
             }
         }
     }
@@ -68,11 +89,17 @@ impl GameService for GrpcServer {
         let (in_sender, in_rec) = mpsc::channel(128);
         let (out_sender, out_rec) = mpsc::channel(128);
 
-        tokio::spawn(async move {
-            while let Some(result) = in_stream.next().await {
-                let _ = in_sender.send(result).await;
-            }
-        });
+        // Players name the table they join first; an empty id takes any
+        // table waiting for players. Options open a table of their own.
+        let join = match in_stream.next().await {
+            Some(Ok(Message {
+                content: Some(message::Content::Join(join)),
+            })) => join,
+            _ => return Err(Status::invalid_argument("Join a game first")),
+        };
+        self.manager
+            .check(&join.options)
+            .map_err(|error| Status::invalid_argument(error.to_string()))?;
 
         let remote_player = Box::new(GrpcPlayer {
             receiver: in_rec,
@@ -80,7 +107,15 @@ impl GameService for GrpcServer {
             number: 0,
         });
 
-        let _ = self.game_handler.send(remote_player).await;
+        self.manager
+            .join(&join.game_id, &join.options, remote_player)
+            .map_err(|error| Status::unavailable(error.to_string()))?;
+
+        tokio::spawn(async move {
+            while let Some(result) = in_stream.next().await {
+                let _ = in_sender.send(result).await;
+            }
+        });
 
         let output_stream = ReceiverStream::new(out_rec);
         Ok(Response::new(
@@ -89,11 +124,10 @@ impl GameService for GrpcServer {
     }
 }
 
-pub async fn start_grpc() -> Result<(), Box<dyn std::error::Error>> {
+/// Serves the tables of the lobby.
+pub async fn start_grpc(manager: GameManager) -> Result<(), Box<dyn std::error::Error>> {
     let addr = "[::1]:50051".parse()?;
-    let game_handler = start_game();
-
-    let greeter = GrpcServer { game_handler };
+    let greeter = GrpcServer { manager };
 
     Server::builder()
         .add_service(GameServiceServer::new(greeter))
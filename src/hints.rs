@@ -0,0 +1,72 @@
+use crate::bots::pimc::PimcStrategy;
+use crate::bots::view::PlayerView;
+use crate::game::Move;
+
+/// Points a move may be expected to give away before the coach speaks up.
+pub const BLUNDER: f64 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hint {
+    pub move_: Move,
+    /// Expected result of the hand for the player's side after the move.
+    pub value: f64,
+}
+
+/// The player's legal moves, best first, as the PIMC bot rates them.
+pub fn hints(view: &PlayerView, seed: u64) -> Vec<Hint> {
+    let mut hints = PimcStrategy::new(seed)
+        .evaluate(view)
+        .into_iter()
+        .map(|(move_, value)| Hint { move_, value })
+        .collect::<Vec<_>>();
+
+    hints.sort_by(|a, b| b.value.total_cmp(&a.value));
+    hints
+}
+
+/// The best move and the chosen one, when the chosen one gives away more
+/// than `BLUNDER` points.
+pub fn blunder(hints: &[Hint], move_: &Move) -> Option<(Hint, Hint)> {
+    let best = *hints.first()?;
+    let chosen = *hints.iter().find(|hint| hint.move_ == *move_)?;
+
+    match best.value - chosen.value > BLUNDER {
+        true => Some((best, chosen)),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::solver::solve;
+    use crate::game::Game;
+    use crate::rules::Rules;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_hints_match_the_solver() {
+        // Two players share the whole set, so seat 0 knows every tile and
+        // the hints late in the hand are exact.
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut game = Game::deal_with(Rules::block(), 2, 0, &mut rng);
+        let mut view = PlayerView::new(Rules::block(), 2, 0, 0, game.players[0].clone());
+
+        while game.players[0].len() + game.players[1].len() > 10 || game.next != 0 {
+            let turn = game.next as usize;
+            let update = game.play(&game.legal_moves(turn)[0]).unwrap();
+            view.apply(turn, &update).unwrap();
+            assert!(game.outcome().is_none());
+        }
+
+        let hints = hints(&view, 1);
+        let solution = solve(&game).unwrap();
+
+        assert_eq!(solution.value as f64, hints[0].value);
+        for hint in &hints {
+            let gives_away = hints[0].value - hint.value;
+            assert_eq!(gives_away > BLUNDER, blunder(&hints, &hint.move_).is_some());
+        }
+    }
+}
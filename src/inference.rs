@@ -0,0 +1,450 @@
+use crate::game::{Domino, Game, Update};
+use crate::rules::Rules;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Attempts at a deal that keeps to everything known before giving up on
+/// the constraints and dealing to the hand counts alone.
+const MAX_TRIES: usize = 100;
+
+/// Somewhere to deal tiles into: a seat or the boneyard, with its room and
+/// the tiles it may hold.
+type Place<'a> = (Option<usize>, usize, Option<&'a Vec<Domino>>);
+
+/// Tiles that came into a hand together and are known to fit the same
+/// constraints, such as the dealt hand or a single draw.
+#[derive(Debug, Clone, PartialEq)]
+struct Group {
+    size: usize,
+    candidates: Vec<Domino>,
+}
+
+/// One way the unseen tiles could lie, consistent with the play so far.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deal {
+    pub hands: [Vec<Domino>; 4],
+    pub boneyard: Vec<Domino>,
+}
+
+/// How likely each place is to hold a tile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Odds {
+    pub domino: Domino,
+    pub seats: [f64; 4],
+    pub boneyard: f64,
+}
+
+/// Follows the tiles of a hand from one seat's point of view, or from the
+/// rail when there is no seat, keeping what every other seat could still
+/// hold. A seat passing on an end holds nothing that fits it; tiles it draws
+/// afterwards may be anything.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tracker {
+    rules: Rules,
+    seat: Option<usize>,
+    n_players: usize,
+    hand: Vec<Domino>,
+    unseen: Vec<Domino>,
+    groups: [Vec<Group>; 4],
+    boneyard: usize,
+}
+
+impl Tracker {
+    /// Starts tracking a freshly dealt hand. Only the observer's tiles are
+    /// read from the game, the others are only counted.
+    pub fn new(game: &Game, seat: Option<usize>) -> Tracker {
+        let rules = game.rules.clone();
+        let n_players = game.n_players() as usize;
+        let hand: Vec<Domino> = seat
+            .map(|seat| game.players[seat].iter().map(|d| d.normalized()).collect())
+            .unwrap_or_default();
+
+        let unseen = (0..=rules.max_pip)
+            .flat_map(|i| (0..(i + 1)).map(move |j| Domino(i, j)))
+            .filter(|domino| !hand.contains(domino))
+            .collect::<Vec<_>>();
+
+        let mut groups: [Vec<Group>; 4] = Default::default();
+        for (other, groups) in groups.iter_mut().enumerate().take(n_players) {
+            if Some(other) != seat {
+                groups.push(Group {
+                    size: game.players[other].len(),
+                    candidates: unseen.clone(),
+                });
+            }
+        }
+
+        Tracker {
+            rules,
+            seat,
+            n_players,
+            hand,
+            unseen,
+            groups,
+            boneyard: game.boneyard.len(),
+        }
+    }
+
+    /// Tiles neither on the board nor in the observer's hand.
+    pub fn unseen(&self) -> &[Domino] {
+        &self.unseen
+    }
+
+    /// Tiles the given seat could be holding.
+    pub fn candidates(&self, seat: usize) -> Vec<Domino> {
+        if Some(seat) == self.seat {
+            return self.hand.clone();
+        }
+
+        let mut candidates = self.groups[seat]
+            .iter()
+            .flat_map(|group| group.candidates.iter().copied())
+            .collect::<Vec<_>>();
+        candidates.sort_by_key(|domino| (domino.0, domino.1));
+        candidates.dedup();
+        candidates
+    }
+
+    pub fn could_hold(&self, seat: usize, domino: &Domino) -> bool {
+        self.candidates(seat).contains(&domino.normalized())
+    }
+
+    pub fn hand_size(&self, seat: usize) -> usize {
+        match Some(seat) == self.seat {
+            true => self.hand.len(),
+            false => self.groups[seat].iter().map(|group| group.size).sum(),
+        }
+    }
+
+    pub fn boneyard(&self) -> usize {
+        self.boneyard
+    }
+
+    /// Takes in a move, given the game as it stood before it.
+    pub fn observe(&mut self, before: &Game, player: usize, update: &Update) {
+        let domino = match *update {
+            Update::Left(domino) | Update::Right(domino) | Update::Branch(_, domino) => domino,
+            Update::Skip => {
+                self.rule_out(before, player);
+                return;
+            }
+            Update::Draw => {
+                self.rule_out(before, player);
+                self.boneyard = self.boneyard.saturating_sub(1);
+                if Some(player) != self.seat {
+                    self.groups[player].push(Group {
+                        size: 1,
+                        candidates: self.unseen.clone(),
+                    });
+                }
+                return;
+            }
+        };
+
+        let domino = domino.normalized();
+
+        if Some(player) == self.seat {
+            self.hand.retain(|tile| *tile != domino);
+        } else {
+            self.take_from(player, domino);
+        }
+
+        self.unseen.retain(|tile| *tile != domino);
+        for groups in self.groups.iter_mut() {
+            for group in groups.iter_mut() {
+                group.candidates.retain(|tile| *tile != domino);
+            }
+        }
+    }
+
+    /// Records a tile the observer drew.
+    pub fn reveal(&mut self, domino: Domino) {
+        let domino = domino.normalized();
+
+        self.unseen.retain(|tile| *tile != domino);
+        for groups in self.groups.iter_mut() {
+            for group in groups.iter_mut() {
+                group.candidates.retain(|tile| *tile != domino);
+            }
+        }
+        self.hand.push(domino);
+    }
+
+    /// The seat holds nothing that fits the open ends.
+    fn rule_out(&mut self, before: &Game, player: usize) {
+        let ends = before.open_ends();
+        let rules = &self.rules;
+
+        for group in self.groups[player].iter_mut() {
+            group.candidates.retain(|tile| {
+                ends.iter()
+                    .all(|(_, number)| rules.place(tile, *number).is_none())
+            });
+        }
+    }
+
+    /// The tile left one of the seat's groups. Not knowing which, every group
+    /// that could have held it is merged into one.
+    fn take_from(&mut self, player: usize, domino: Domino) {
+        let groups = std::mem::take(&mut self.groups[player]);
+        let (holding, mut rest): (Vec<_>, Vec<_>) = groups
+            .into_iter()
+            .partition(|group| group.candidates.contains(&domino));
+
+        if holding.is_empty() {
+            // What was known turned out wrong; keep the count right anyway.
+            if let Some(group) = rest.first_mut() {
+                group.size -= 1;
+            }
+        } else {
+            let mut merged = Group {
+                size: 0,
+                candidates: Vec::new(),
+            };
+            for group in holding {
+                merged.size += group.size;
+                merged.candidates.extend(group.candidates);
+            }
+            merged.candidates.sort_by_key(|domino| (domino.0, domino.1));
+            merged.candidates.dedup();
+            merged.size -= 1;
+            rest.push(merged);
+        }
+
+        rest.retain(|group| group.size > 0);
+        self.groups[player] = rest;
+    }
+
+    /// Deals the unseen tiles at random into the other hands and the
+    /// boneyard, keeping to what is known. Tiles with the fewest places to
+    /// go are dealt first and each goes to a place with weight by the room
+    /// left in it, which is close to uniform over the consistent deals.
+    pub fn deal(&self, rng: &mut impl Rng) -> Deal {
+        // Places to deal into: each group of each seat, then the boneyard.
+        let mut places = Vec::new();
+        for (seat, groups) in self.groups.iter().enumerate().take(self.n_players) {
+            for group in groups {
+                places.push((Some(seat), group.size, Some(&group.candidates)));
+            }
+        }
+        places.push((None, self.boneyard, None));
+
+        for _ in 0..MAX_TRIES {
+            if let Some(deal) = self.deal_into(&places, true, rng) {
+                return deal;
+            }
+        }
+
+        // What is known does not hold together: deal to the counts alone.
+        self.deal_into(&places, false, rng)
+            .expect("An unconstrained deal always goes through")
+    }
+
+    /// One attempt at a deal into the given places, giving up when a tile
+    /// fits nowhere with room left. Without keeping to what is known it
+    /// always goes through, any tiles beyond the room going to the boneyard.
+    fn deal_into(&self, places: &[Place], strict: bool, rng: &mut impl Rng) -> Option<Deal> {
+        let fits = |place: usize, domino: &Domino| {
+            places[place]
+                .2
+                .map(|candidates| candidates.contains(domino))
+                .unwrap_or(true)
+        };
+
+        let mut tiles = self.unseen.clone();
+        tiles.shuffle(rng);
+        if strict {
+            tiles.sort_by_key(|domino| {
+                (0..places.len())
+                    .filter(|place| fits(*place, domino))
+                    .count()
+            });
+        }
+
+        let mut room = places.iter().map(|place| place.1).collect::<Vec<_>>();
+        let mut deal = Deal {
+            hands: Default::default(),
+            boneyard: Vec::new(),
+        };
+
+        for domino in tiles {
+            let open = (0..places.len())
+                .filter(|place| room[*place] > 0 && (!strict || fits(*place, &domino)))
+                .collect::<Vec<_>>();
+
+            let place = match open.choose_weighted(rng, |place| room[*place]) {
+                Ok(place) => *place,
+                Err(_) if strict => return None,
+                Err(_) => {
+                    deal.boneyard.push(domino);
+                    continue;
+                }
+            };
+
+            room[place] -= 1;
+            match places[place].0 {
+                Some(seat) => deal.hands[seat].push(domino),
+                None => deal.boneyard.push(domino),
+            }
+        }
+
+        Some(deal)
+    }
+
+    /// Chances of each unseen tile being in each hand or in the boneyard,
+    /// estimated over the given number of sampled deals.
+    pub fn odds(&self, samples: usize, rng: &mut impl Rng) -> Vec<Odds> {
+        let mut odds = self
+            .unseen
+            .iter()
+            .map(|domino| Odds {
+                domino: *domino,
+                seats: [0.0; 4],
+                boneyard: 0.0,
+            })
+            .collect::<Vec<_>>();
+        let share = 1.0 / samples.max(1) as f64;
+
+        for _ in 0..samples {
+            let deal = self.deal(rng);
+            for odds in odds.iter_mut() {
+                match deal
+                    .hands
+                    .iter()
+                    .position(|hand| hand.contains(&odds.domino))
+                {
+                    Some(seat) => odds.seats[seat] += share,
+                    None => odds.boneyard += share,
+                }
+            }
+        }
+
+        odds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn table() -> (Game, Tracker) {
+        let mut game = Game::deal(Rules::block(), 4, 0);
+        game.players = [
+            vec![
+                Domino(6, 6),
+                Domino(6, 1),
+                Domino(5, 0),
+                Domino(2, 2),
+                Domino(3, 1),
+                Domino(4, 0),
+                Domino(1, 0),
+            ],
+            vec![
+                Domino(5, 5),
+                Domino(5, 4),
+                Domino(5, 3),
+                Domino(5, 2),
+                Domino(5, 1),
+                Domino(4, 4),
+                Domino(4, 3),
+            ],
+            vec![
+                Domino(6, 5),
+                Domino(6, 4),
+                Domino(6, 3),
+                Domino(6, 2),
+                Domino(6, 0),
+                Domino(3, 3),
+                Domino(2, 1),
+            ],
+            vec![
+                Domino(4, 2),
+                Domino(4, 1),
+                Domino(3, 2),
+                Domino(3, 0),
+                Domino(2, 0),
+                Domino(1, 1),
+                Domino(0, 0),
+            ],
+        ];
+        let tracker = Tracker::new(&game, Some(0));
+        (game, tracker)
+    }
+
+    fn play(game: &mut Game, tracker: &mut Tracker, move_: &str) {
+        let before = game.clone();
+        let move_ = crate::game::Move::parse(move_).unwrap();
+        let update = game.play(&move_).unwrap();
+        tracker.observe(&before, move_.player(), &update);
+    }
+
+    #[test]
+    fn test_pass_rules_out_numbers() {
+        let (mut game, mut tracker) = table();
+
+        play(&mut game, &mut tracker, "left 0 0");
+        assert_eq!(21, tracker.unseen().len());
+
+        // Seat 1 holds no 6 and has to pass.
+        play(&mut game, &mut tracker, "pass 1");
+
+        assert!(!tracker.could_hold(1, &Domino(6, 5)));
+        assert!(tracker.could_hold(2, &Domino(6, 5)));
+        assert!(tracker.could_hold(1, &Domino(0, 0)));
+        assert_eq!(7, tracker.hand_size(1));
+    }
+
+    #[test]
+    fn test_deals_keep_to_what_is_known() {
+        let (mut game, mut tracker) = table();
+        play(&mut game, &mut tracker, "left 0 0");
+        play(&mut game, &mut tracker, "pass 1");
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for _ in 0..20 {
+            let deal = tracker.deal(&mut rng);
+
+            assert!(deal.hands[1].iter().all(|domino| domino.0 != 6));
+            assert_eq!([0, 7, 7, 7], deal.hands.clone().map(|hand| hand.len()));
+            assert!(deal.boneyard.is_empty());
+        }
+
+        for odds in tracker.odds(50, &mut rng) {
+            let total = odds.seats.iter().sum::<f64>() + odds.boneyard;
+            assert!((total - 1.0).abs() < 1e-9);
+            if odds.domino.0 == 6 {
+                assert_eq!(0.0, odds.seats[1]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_deals_match_the_counts_when_nothing_fits() {
+        let (mut game, mut tracker) = table();
+        play(&mut game, &mut tracker, "left 0 0");
+        // Every other seat known to hold only 6-5: no deal keeps to that.
+        for groups in &mut tracker.groups[1..] {
+            groups[0].candidates = vec![Domino(6, 5)];
+        }
+        let mut rng = StdRng::seed_from_u64(3);
+
+        let deal = tracker.deal(&mut rng);
+
+        assert_eq!([0, 7, 7, 7], deal.hands.clone().map(|hand| hand.len()));
+        assert!(deal.boneyard.is_empty());
+        let mut dealt = deal.hands.concat();
+        dealt.sort_by_key(|domino| (domino.0, domino.1));
+        let mut unseen = tracker.unseen().to_vec();
+        unseen.sort_by_key(|domino| (domino.0, domino.1));
+        assert_eq!(unseen, dealt);
+
+        // A tile too many for the hands is left in the boneyard.
+        tracker.groups[3][0].size = 6;
+        let deal = tracker.deal(&mut rng);
+
+        assert_eq!([0, 7, 7, 6], deal.hands.clone().map(|hand| hand.len()));
+        assert_eq!(1, deal.boneyard.len());
+    }
+}
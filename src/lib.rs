@@ -1,3 +1,13 @@
+pub mod analysis;
+pub mod arena;
+pub mod bots;
 pub mod concurrent;
+pub mod forecast;
 pub mod game;
 pub mod grpc;
+pub mod hints;
+pub mod inference;
+pub mod lobby;
+pub mod log;
+pub mod rules;
+pub mod scoring;
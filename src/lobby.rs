@@ -0,0 +1,330 @@
+use crate::bots::levels::{table_options, TableOptions};
+use crate::concurrent::{spawn_table, RemotePlayer, Spectators, Watch};
+use crate::scoring::Match;
+use anyhow::{anyhow, Result};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+
+/// Seats at every table of the lobby.
+pub const SEATS: usize = 4;
+
+/// Table options a player may give for a table it opens, on top of the
+/// server's: bots of the levels `bot=<level>` take seats of it.
+pub const PLAYER_OPTIONS: [&str; 3] = ["wait", "fill", "bot"];
+
+/// A table as the lobby lists it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableInfo {
+    pub id: String,
+    /// Seats taken by players and by the bots of the table options. Bots
+    /// sat down after the wait hold the others only until players come.
+    pub seated: usize,
+    pub seats: usize,
+    /// The table options given by the player who opened it.
+    pub options: String,
+}
+
+impl TableInfo {
+    /// Whether nobody else can join.
+    pub fn full(&self) -> bool {
+        self.seated >= self.seats
+    }
+}
+
+#[derive(Debug)]
+struct Table {
+    players: Sender<Box<dyn RemotePlayer>>,
+    seated: usize,
+    task: JoinHandle<Option<Match>>,
+    options: String,
+    spectators: Spectators,
+}
+
+#[derive(Debug, Default)]
+struct Tables {
+    tables: BTreeMap<String, Table>,
+    /// Tables opened so far, to name the next one.
+    opened: u64,
+}
+
+/// Hosts any number of tables at once. Players join a table by its id, or
+/// any table still waiting for players when they give none; a table is
+/// opened for an id nobody has used yet, or when every table is full.
+/// Tables are dropped from the lobby once their match is over. With
+/// `wait=` in the options, a table starts with bots in the empty seats
+/// after the wait and still takes players, who replace the bots.
+///
+/// Every table is played under the table options of the server, bots
+/// included, as `table_options` reads them, and those of `PLAYER_OPTIONS`
+/// the player who opened it gave. The manager is cheap to clone and clones
+/// share the tables.
+#[derive(Debug, Clone)]
+pub struct GameManager {
+    options: String,
+    tables: Arc<Mutex<Tables>>,
+}
+
+impl GameManager {
+    pub fn new(options: &str) -> Result<GameManager> {
+        let manager = GameManager {
+            options: options.to_string(),
+            tables: Arc::new(Mutex::new(Tables::default())),
+        };
+        manager.check("")?;
+
+        Ok(manager)
+    }
+
+    /// Opens a table, under the given id or a new one, with the given
+    /// player options, and returns its id.
+    pub fn create(&self, id: Option<&str>, options: &str) -> Result<String> {
+        let mut tables = self.tables.lock().unwrap();
+        self.open(&mut tables, id, options)
+    }
+
+    /// Checks the options a player gives for a table: only the ones of
+    /// `PLAYER_OPTIONS`, with values the server takes.
+    pub fn check(&self, options: &str) -> Result<()> {
+        self.table_options(options).map(|_| ())
+    }
+
+    fn table_options(&self, options: &str) -> Result<TableOptions> {
+        for word in options.split_whitespace() {
+            let key = word.split_once('=').map_or(word, |(key, _)| key);
+            if !PLAYER_OPTIONS.contains(&key) {
+                return Err(anyhow!("{} cannot be set for a table", word));
+            }
+        }
+
+        let options = table_options(&format!("{} {}", self.options, options), SEATS as i32)?;
+        if options.bots.len() >= SEATS {
+            return Err(anyhow!("No seats left for players".to_string()));
+        }
+        Ok(options)
+    }
+
+    fn open(&self, tables: &mut Tables, id: Option<&str>, options: &str) -> Result<String> {
+        let id = match id {
+            Some(id) => id.to_string(),
+            None => loop {
+                tables.opened += 1;
+                let id = tables.opened.to_string();
+                if !tables.tables.contains_key(&id) {
+                    break id;
+                }
+            },
+        };
+        if tables.tables.contains_key(&id) {
+            return Err(anyhow!("Table {} already exists", id));
+        }
+
+        let player_options = options.split_whitespace().collect::<Vec<_>>().join(" ");
+        let options = self.table_options(options)?;
+        let (players, task, spectators) =
+            spawn_table(options.rules, SEATS, options.fill, options.clock);
+        let mut table = Table {
+            players,
+            seated: 0,
+            task,
+            options: player_options,
+            spectators,
+        };
+        for bot in options.bots {
+            seat(&mut table, bot)?;
+        }
+
+        tables.tables.insert(id.clone(), table);
+        Ok(id)
+    }
+
+    /// Every table in the lobby, by id.
+    pub fn list(&self) -> Vec<TableInfo> {
+        let mut tables = self.tables.lock().unwrap();
+        reclaim(&mut tables);
+
+        tables
+            .tables
+            .iter()
+            .map(|(id, table)| TableInfo {
+                id: id.clone(),
+                seated: table.seated,
+                seats: SEATS,
+                options: table.options.clone(),
+            })
+            .collect()
+    }
+
+    /// Seats a player at the table with the given id, opening it if there
+    /// is none, or at any table waiting for players when the id is empty.
+    /// Player options open a table of their own: they are refused for a
+    /// table that is already open, and an empty id with options opens a new
+    /// one. Returns the id of the table.
+    pub fn join(&self, id: &str, options: &str, player: Box<dyn RemotePlayer>) -> Result<String> {
+        let mut tables = self.tables.lock().unwrap();
+        reclaim(&mut tables);
+        let opening = !options.trim().is_empty();
+
+        let id = match id {
+            "" if opening => self.open(&mut tables, None, options)?,
+            "" => match tables.tables.iter().find(|(_, table)| table.seated < SEATS) {
+                Some((id, _)) => id.clone(),
+                None => self.open(&mut tables, None, options)?,
+            },
+            id if !tables.tables.contains_key(id) => self.open(&mut tables, Some(id), options)?,
+            id if opening => return Err(anyhow!("Table {} is already open", id)),
+            id => id.to_string(),
+        };
+
+        let table = tables.tables.get_mut(&id).unwrap();
+        if table.seated >= SEATS {
+            return Err(anyhow!("Table {} is full", id));
+        }
+        seat(table, player)?;
+
+        Ok(id)
+    }
+
+    /// Looks on at the table with the given id: every move made at it, and
+    /// the win bar after it.
+    pub fn watch(&self, id: &str) -> Result<broadcast::Receiver<Watch>> {
+        let mut tables = self.tables.lock().unwrap();
+        reclaim(&mut tables);
+
+        let table = tables
+            .tables
+            .get(id)
+            .ok_or_else(|| anyhow!("No table {}", id))?;
+        Ok(table.spectators.subscribe())
+    }
+
+    /// Tears a table down, ending its match if it is being played.
+    pub fn close(&self, id: &str) -> Result<()> {
+        let mut tables = self.tables.lock().unwrap();
+        let table = tables
+            .tables
+            .remove(id)
+            .ok_or_else(|| anyhow!("No table {}", id))?;
+
+        table.task.abort();
+        Ok(())
+    }
+}
+
+fn seat(table: &mut Table, player: Box<dyn RemotePlayer>) -> Result<()> {
+    table
+        .players
+        .try_send(player)
+        .map_err(|_| anyhow!("The table is closed".to_string()))?;
+    table.seated += 1;
+    Ok(())
+}
+
+/// Drops the tables whose match is over.
+fn reclaim(tables: &mut Tables) {
+    tables.tables.retain(|_, table| !table.task.is_finished());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::heuristics::random_bot;
+    use crate::rules::Rules;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_tables_by_id() {
+        let manager = GameManager::new("bot=beginner bot=beginner block target=20").unwrap();
+        let player = |seed| random_bot(Rules::block(), 4, seed);
+
+        let first = manager.join("", "", player(1)).unwrap();
+        assert_eq!(first, manager.join("", "", player(2)).unwrap());
+        let second = manager.join("", "", player(3)).unwrap();
+        assert_ne!(first, second);
+        assert_eq!("club", manager.join("club", "", player(4)).unwrap());
+
+        let tables = manager.list();
+        assert_eq!(3, tables.len());
+        assert!(manager.join(&first, "", player(5)).is_err());
+        assert!(manager.create(Some("club"), "").is_err());
+
+        manager.close(&second).unwrap();
+        assert!(manager.close(&second).is_err());
+
+        // The full table plays its match and is reclaimed.
+        for _ in 0..200 {
+            if manager.list().iter().all(|table| table.id != first) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let ids = manager
+            .list()
+            .into_iter()
+            .map(|table| table.id)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["club".to_string()], ids);
+        assert!(GameManager::new("bot=easy bot=easy bot=easy bot=easy").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bots_chosen_by_players() {
+        let manager = GameManager::new("bot=beginner block target=20").unwrap();
+        let player = |seed| random_bot(Rules::block(), 4, seed);
+
+        let bots = "bot=easy bot=intermediate";
+        assert_eq!("bots", manager.join("bots", bots, player(1)).unwrap());
+        let table = manager.list().into_iter().next().unwrap();
+        assert!(table.full());
+        assert_eq!(bots, table.options);
+
+        assert!(manager.check("bot=expert bot=expert bot=expert").is_err());
+        assert!(manager.check("bot=grandmaster").is_err());
+        assert!(manager.check("bot=hard").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_options_per_table() {
+        let manager = GameManager::new("block target=20").unwrap();
+        let player = |seed| random_bot(Rules::block(), 4, seed);
+
+        let options = "wait=0.05 fill=beginner";
+        assert_eq!("quick", manager.join("quick", options, player(1)).unwrap());
+        assert_eq!("slow", manager.join("slow", "", player(2)).unwrap());
+        assert_eq!("1", manager.join("", "wait=60", player(3)).unwrap());
+
+        assert!(manager.join("slow", "wait=1", player(4)).is_err());
+        assert!(manager.join("other", "target=5", player(5)).is_err());
+        assert!(manager
+            .join("other", "fill=grandmaster", player(6))
+            .is_err());
+        assert!(manager.check("engine=./engine").is_err());
+        assert!(manager.check("wait=x").is_err());
+        let tables = manager
+            .list()
+            .into_iter()
+            .map(|table| (table.id, table.options))
+            .collect::<Vec<_>>();
+        let expected = [("1", "wait=60"), ("quick", options), ("slow", "")];
+        assert_eq!(
+            expected.map(|(id, options)| (id.to_string(), options.to_string())),
+            tables[..]
+        );
+
+        // Only the table that waits fills up with bots and plays.
+        for _ in 0..200 {
+            if manager.list().iter().all(|table| table.id != "quick") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let ids = manager
+            .list()
+            .into_iter()
+            .map(|table| table.id)
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["1".to_string(), "slow".to_string()], ids);
+    }
+}
@@ -0,0 +1,304 @@
+use crate::game::{Domino, Game, Move};
+use crate::rules::Misdeal;
+use crate::scoring::HandResult;
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Deal {
+        hand: usize,
+        players: [Vec<Domino>; 4],
+        boneyard: Vec<Domino>,
+        next: i32,
+    },
+    /// The previous deal was thrown in because of `seat`'s hand.
+    Misdeal {
+        seat: usize,
+        misdeal: Misdeal,
+    },
+    Move(Move),
+    HandOver(HandResult),
+}
+
+/// Everything that happened at a table, in order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GameLog {
+    pub events: Vec<Event>,
+}
+
+impl GameLog {
+    pub fn new() -> GameLog {
+        GameLog::default()
+    }
+
+    pub fn deal(&mut self, hand: usize, game: &Game) {
+        self.events.push(Event::Deal {
+            hand,
+            players: game.players.clone(),
+            boneyard: game.boneyard.clone(),
+            next: game.next,
+        });
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// Every deal of the given hand, thrown-in ones included.
+    pub fn deals(&self, hand: usize) -> Vec<&[Vec<Domino>; 4]> {
+        self.events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Deal {
+                    hand: dealt,
+                    players,
+                    ..
+                } if *dealt == hand => Some(players),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Reads a log written in the game notation, one event a line:
+    ///
+    /// ```text
+    /// deal 0 next 1
+    /// seat 0: 6-6 5-4 3-1
+    /// seat 1: 2-2 6-1 4-0
+    /// boneyard: 5-5 3-0
+    /// misdeal 1 doubles 5
+    /// left 1 0
+    /// draw 0
+    /// ```
+    ///
+    /// Moves are written as `Move::parse` reads them. Blank lines and lines
+    /// starting with `#` are skipped. Hand results are not part of the
+    /// notation, replaying the moves gives them again. Seats are dealt from
+    /// 0 up, and the player to move, misdeals and moves must name one of
+    /// them.
+    pub fn parse(text: &str) -> Result<GameLog> {
+        let mut log = GameLog::new();
+        // The line of the deal still being read, and the seats of the last.
+        let mut deal = None;
+        let mut seats = 4;
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let error = || anyhow!("Line {}: cannot read {}", number + 1, line);
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            if !matches!(words.first(), Some(&"seat") | Some(&"boneyard:")) {
+                if let (Some(deal), Some(Event::Deal { players, next, .. })) =
+                    (deal.take(), log.events.last())
+                {
+                    seats = dealt_seats(players, *next, deal)?;
+                }
+            }
+
+            match words.as_slice() {
+                ["deal", hand, "next", next] => {
+                    log.push(Event::Deal {
+                        hand: hand.parse().map_err(|_| error())?,
+                        players: Default::default(),
+                        boneyard: Vec::new(),
+                        next: next.parse().map_err(|_| error())?,
+                    });
+                    deal = Some(number + 1);
+                }
+                ["seat", seat, tiles @ ..] => {
+                    let seat = seat
+                        .trim_end_matches(':')
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|seat| *seat < 4)
+                        .ok_or_else(error)?;
+                    match log.events.last_mut() {
+                        Some(Event::Deal { players, .. }) => {
+                            players[seat] = parse_tiles(tiles).ok_or_else(error)?
+                        }
+                        _ => return Err(error()),
+                    }
+                }
+                ["boneyard:", tiles @ ..] => match log.events.last_mut() {
+                    Some(Event::Deal { boneyard, .. }) => {
+                        *boneyard = parse_tiles(tiles).ok_or_else(error)?
+                    }
+                    _ => return Err(error()),
+                },
+                ["misdeal", seat, kind, value] => {
+                    let misdeal = match *kind {
+                        "doubles" => Misdeal::Doubles(value.parse().map_err(|_| error())?),
+                        "pips" => Misdeal::FewPips(value.parse().map_err(|_| error())?),
+                        _ => return Err(error()),
+                    };
+                    log.push(Event::Misdeal {
+                        seat: seat
+                            .parse()
+                            .ok()
+                            .filter(|seat| *seat < seats)
+                            .ok_or_else(error)?,
+                        misdeal,
+                    });
+                }
+                _ => {
+                    let move_ = Move::parse(line)
+                        .filter(|move_| move_.player() < seats)
+                        .ok_or_else(error)?;
+                    log.push(Event::Move(move_));
+                }
+            }
+        }
+
+        if let (Some(deal), Some(Event::Deal { players, next, .. })) = (deal, log.events.last()) {
+            dealt_seats(players, *next, deal)?;
+        }
+
+        Ok(log)
+    }
+}
+
+/// Checks a deal read from the given line once its seats are all in, and
+/// returns how many seats it dealt.
+fn dealt_seats(players: &[Vec<Domino>; 4], next: i32, line: usize) -> Result<usize> {
+    let seats = players.iter().take_while(|tiles| !tiles.is_empty()).count();
+
+    if let Some(seat) = (seats..players.len()).find(|seat| !players[*seat].is_empty()) {
+        return Err(anyhow!(
+            "Line {}: seat {} dealt but not seat {}",
+            line,
+            seat,
+            seats
+        ));
+    }
+    if !(0..seats as i32).contains(&next) {
+        return Err(anyhow!(
+            "Line {}: seat {} to move was not dealt",
+            line,
+            next
+        ));
+    }
+
+    Ok(seats)
+}
+
+fn parse_tiles(tiles: &[&str]) -> Option<Vec<Domino>> {
+    tiles.iter().map(|tile| Domino::parse(tile)).collect()
+}
+
+fn write_tiles(f: &mut fmt::Formatter, tiles: &[Domino]) -> fmt::Result {
+    for tile in tiles {
+        write!(f, " {}", tile)?;
+    }
+    writeln!(f)
+}
+
+/// Writes the log in the game notation read by `GameLog::parse`.
+impl fmt::Display for GameLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for event in &self.events {
+            match event {
+                Event::Deal {
+                    hand,
+                    players,
+                    boneyard,
+                    next,
+                } => {
+                    writeln!(f, "deal {} next {}", hand, next)?;
+                    for (seat, tiles) in players.iter().enumerate() {
+                        if !tiles.is_empty() {
+                            write!(f, "seat {}:", seat)?;
+                            write_tiles(f, tiles)?;
+                        }
+                    }
+                    write!(f, "boneyard:")?;
+                    write_tiles(f, boneyard)?;
+                }
+                Event::Misdeal { seat, misdeal } => match misdeal {
+                    Misdeal::Doubles(doubles) => {
+                        writeln!(f, "misdeal {} doubles {}", seat, doubles)?
+                    }
+                    Misdeal::FewPips(pips) => writeln!(f, "misdeal {} pips {}", seat, pips)?,
+                },
+                Event::Move(move_) => writeln!(f, "{}", move_)?,
+                Event::HandOver(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "\
+deal 0 next 1
+seat 0: 6-6 5-4
+seat 1: 5-5 1-1
+boneyard: 3-0
+misdeal 1 doubles 2
+deal 0 next 0
+seat 0: 6-6 5-4
+seat 1: 5-5 1-0
+boneyard: 3-0
+left 0 0
+draw 1
+branch 1 0 2
+pass 0
+";
+
+    #[test]
+    fn test_notation_round_trip() {
+        let log = GameLog::parse(RECORD).unwrap();
+
+        assert_eq!(RECORD, log.to_string());
+        assert_eq!(log, GameLog::parse(&log.to_string()).unwrap());
+    }
+
+    #[test]
+    fn test_misdeal_and_seat_lines() {
+        let log = GameLog::parse(RECORD).unwrap();
+
+        assert_eq!(2, log.deals(0).len());
+        assert_eq!(vec![Domino(5, 5), Domino(1, 1)], log.deals(0)[0][1]);
+        assert_eq!(
+            Event::Misdeal {
+                seat: 1,
+                misdeal: Misdeal::Doubles(2),
+            },
+            log.events[1]
+        );
+        assert_eq!(Event::Move(Move::Branch(1, 0, 2)), log.events[5]);
+    }
+
+    #[test]
+    fn test_malformed_lines() {
+        let deal = "deal 0 next 0\nseat 0: 6-6\nseat 1: 5-5\nboneyard:\n";
+
+        for (text, line) in [
+            (
+                "deal 0 next 7\nseat 0: 6-6 5-4\nseat 1: 5-5 1-1\nboneyard:\nleft 7 0",
+                1,
+            ),
+            ("deal 0 next -1\nseat 0: 6-6\nseat 1: 5-5", 1),
+            ("deal 0 next 0\nseat 0: 6-6\nseat 2: 5-5\n", 1),
+            ("deal x next 0", 1),
+            ("seat 0: 6-6", 1),
+            (&format!("{}seat 4: 4-4", deal), 5),
+            (&format!("{}seat 1: 5-x", deal), 5),
+            (&format!("{}left 2 0", deal), 5),
+            (&format!("{}misdeal 2 doubles 5", deal), 5),
+            (&format!("{}misdeal 0 sevens 5", deal), 5),
+            (&format!("{}jump 0", deal), 5),
+        ] {
+            let error = GameLog::parse(text).unwrap_err().to_string();
+            assert!(error.starts_with(&format!("Line {}:", line)), "{}", error);
+        }
+    }
+}
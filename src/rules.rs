@@ -0,0 +1,527 @@
+use crate::game::Domino;
+use anyhow::{anyhow, Result};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Variant {
+    Block,
+    ChickenFoot,
+    Brazilian,
+    Caribbean,
+    Sniff,
+    Matador,
+    Bergen,
+}
+
+/// How the half of a tile has to relate to the open end it is played on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Matching {
+    /// Touching halves show the same number.
+    Equal,
+    /// Touching halves add up to the number. Tiles adding up to it, and the
+    /// double blank, are wild "matadors" that go on any end.
+    SumTo(i32),
+}
+
+/// Who takes a blocked hand ("tranque").
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Tranque {
+    /// The seat with the fewest pips in hand.
+    LowestHand,
+    /// The side with the fewest pips in its hands.
+    LowestTeam,
+    /// The player who blocked the game against the next player: the
+    /// lighter hand wins and the blocker wins ties.
+    Blocker,
+}
+
+/// Points a Brazilian partnership scores depending on how the hand was won.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Bonuses {
+    /// A plain win ("batida"), also scored by the lighter team of a blocked hand.
+    pub batida: i32,
+    /// Going out with a double ("carroça").
+    pub carroca: i32,
+    /// Going out with a tile that fits both ends ("capicua").
+    pub capicua: i32,
+    /// Going out with a double that fits both ends ("lá-e-lô").
+    pub la_e_lo: i32,
+}
+
+impl Default for Bonuses {
+    fn default() -> Bonuses {
+        Bonuses {
+            batida: 1,
+            carroca: 2,
+            capicua: 3,
+            la_e_lo: 4,
+        }
+    }
+}
+
+/// A house rule calling for a redeal when a hand comes out too lopsided.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Misdeal {
+    /// A hand holds at least this many doubles.
+    Doubles(usize),
+    /// A hand holds fewer than this many pips.
+    FewPips(i32),
+}
+
+impl Misdeal {
+    pub fn applies(&self, hand: &[Domino]) -> bool {
+        match *self {
+            Misdeal::Doubles(doubles) => {
+                hand.iter().filter(|domino| domino.is_double()).count() >= doubles
+            }
+            Misdeal::FewPips(pips) => hand.iter().map(|domino| domino.pips()).sum::<i32>() < pips,
+        }
+    }
+}
+
+impl std::fmt::Display for Misdeal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Misdeal::Doubles(doubles) => write!(f, "{} or more doubles", doubles),
+            Misdeal::FewPips(pips) => write!(f, "fewer than {} pips", pips),
+        }
+    }
+}
+
+/// How a match is decided once hands have been scored.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Goal {
+    /// The match ends when a score reaches the target.
+    Target(i32),
+    /// The match ends after a fixed number of hands.
+    Hands(usize),
+    /// Duplicate play: each of this many boards is dealt once and played
+    /// once per seat, the players moving round one seat every time, so that
+    /// everybody holds every hand.
+    Duplicate(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rules {
+    pub variant: Variant,
+    /// Highest pip value of the set: 6 for double-six, 9 for double-nine.
+    pub max_pip: i32,
+    /// Whether a player who cannot play draws from the boneyard.
+    pub draw: bool,
+    /// How many tiles a player may draw per turn; `None` draws until a tile fits.
+    pub max_draws: Option<usize>,
+    pub goal: Goal,
+    /// The match is won by the lowest cumulative score instead of the highest.
+    pub lowest_wins: bool,
+    /// Seats 0 and 2 play against seats 1 and 3.
+    pub partnerships: bool,
+    /// A blocked hand tied between both sides ("galo") scores nothing and adds
+    /// a plain win to whoever wins the next hand.
+    pub galo: bool,
+    pub tranque: Tranque,
+    pub bonuses: Bonuses,
+    /// Deals that are thrown in and reshuffled.
+    pub misdeals: Vec<Misdeal>,
+    /// The first double on the line can be played on from all four sides.
+    pub spinner: bool,
+    /// A play leaving the open ends summing to a multiple of five scores
+    /// that sum, and hands are scored rounded to the nearest five.
+    pub fives: bool,
+    /// Making both ends of the line show the same number scores two points,
+    /// three when one end is the double of it.
+    pub headers: bool,
+    pub matching: Matching,
+    /// Seed for the deals, so that another table can play the same hands.
+    pub deal_seed: Option<u64>,
+}
+
+impl Default for Rules {
+    fn default() -> Rules {
+        Rules::block()
+    }
+}
+
+impl Rules {
+    pub fn block() -> Rules {
+        Rules {
+            variant: Variant::Block,
+            max_pip: 6,
+            draw: false,
+            max_draws: None,
+            goal: Goal::Target(100),
+            lowest_wins: false,
+            partnerships: false,
+            galo: false,
+            tranque: Tranque::LowestTeam,
+            bonuses: Bonuses::default(),
+            misdeals: Vec::new(),
+            spinner: false,
+            fives: false,
+            headers: false,
+            matching: Matching::Equal,
+            deal_seed: None,
+        }
+    }
+
+    /// Double-nine Chicken Foot: every hand opens with the next double down from
+    /// 9-9, each double has to be footed before play continues elsewhere, and
+    /// the lowest total after ten hands wins.
+    pub fn chicken_foot() -> Rules {
+        Rules {
+            variant: Variant::ChickenFoot,
+            max_pip: 9,
+            draw: true,
+            max_draws: Some(1),
+            goal: Goal::Hands(10),
+            lowest_wins: true,
+            partnerships: false,
+            galo: false,
+            tranque: Tranque::LowestTeam,
+            bonuses: Bonuses::default(),
+            misdeals: Vec::new(),
+            spinner: false,
+            fives: false,
+            headers: false,
+            matching: Matching::Equal,
+            deal_seed: None,
+        }
+    }
+
+    /// Brazilian partnership dominoes: the first hand is opened with 6-6, a
+    /// hand is worth one to four points depending on the winning tile, and a
+    /// tied blocked hand carries its points over.
+    pub fn brazilian() -> Rules {
+        Rules {
+            variant: Variant::Brazilian,
+            max_pip: 6,
+            draw: false,
+            max_draws: None,
+            goal: Goal::Target(6),
+            lowest_wins: false,
+            partnerships: true,
+            galo: true,
+            tranque: Tranque::LowestTeam,
+            bonuses: Bonuses::default(),
+            misdeals: Vec::new(),
+            spinner: false,
+            fives: false,
+            headers: false,
+            matching: Matching::Equal,
+            deal_seed: None,
+        }
+    }
+
+    /// Cuban / Caribbean partnership dominoes: double-nine with ten tiles a
+    /// seat and fifteen left out, the winners taking every pip their
+    /// opponents still hold.
+    pub fn caribbean() -> Rules {
+        Rules {
+            variant: Variant::Caribbean,
+            max_pip: 9,
+            draw: false,
+            max_draws: None,
+            goal: Goal::Target(100),
+            lowest_wins: false,
+            partnerships: true,
+            galo: false,
+            tranque: Tranque::LowestTeam,
+            bonuses: Bonuses::default(),
+            misdeals: Vec::new(),
+            spinner: false,
+            fives: false,
+            headers: false,
+            matching: Matching::Equal,
+            deal_seed: None,
+        }
+    }
+
+    /// Sniff: the first double is a spinner, ends summing to a multiple of
+    /// five score as they are made, blocked players draw until they can
+    /// play, and the match goes to 250.
+    pub fn sniff() -> Rules {
+        Rules {
+            variant: Variant::Sniff,
+            max_pip: 6,
+            draw: true,
+            max_draws: None,
+            goal: Goal::Target(250),
+            lowest_wins: false,
+            partnerships: false,
+            galo: false,
+            tranque: Tranque::LowestHand,
+            bonuses: Bonuses::default(),
+            misdeals: Vec::new(),
+            spinner: true,
+            fives: true,
+            headers: false,
+            matching: Matching::Equal,
+            deal_seed: None,
+        }
+    }
+
+    /// Matador: touching ends add up to seven instead of matching, and the
+    /// 0-0, 6-1, 5-2 and 4-3 go anywhere. Blocked players draw.
+    pub fn matador() -> Rules {
+        Rules {
+            variant: Variant::Matador,
+            max_pip: 6,
+            draw: true,
+            max_draws: None,
+            goal: Goal::Target(100),
+            lowest_wins: false,
+            partnerships: false,
+            galo: false,
+            tranque: Tranque::LowestHand,
+            bonuses: Bonuses::default(),
+            misdeals: Vec::new(),
+            spinner: false,
+            fives: false,
+            headers: false,
+            matching: Matching::SumTo(7),
+            deal_seed: None,
+        }
+    }
+
+    /// Bergen: double- and triple-headers score as they are made, going out
+    /// or holding the lightest hand of a blocked game is worth a point, and
+    /// the match goes to 15.
+    pub fn bergen() -> Rules {
+        Rules {
+            variant: Variant::Bergen,
+            max_pip: 6,
+            draw: true,
+            max_draws: None,
+            goal: Goal::Target(15),
+            lowest_wins: false,
+            partnerships: false,
+            galo: false,
+            tranque: Tranque::LowestHand,
+            bonuses: Bonuses::default(),
+            misdeals: Vec::new(),
+            spinner: false,
+            fives: false,
+            headers: true,
+            matching: Matching::Equal,
+            deal_seed: None,
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<Rules> {
+        match name {
+            "block" => Some(Rules::block()),
+            "chicken-foot" | "chickenfoot" => Some(Rules::chicken_foot()),
+            "brazilian" => Some(Rules::brazilian()),
+            "caribbean" | "cuban" => Some(Rules::caribbean()),
+            "sniff" => Some(Rules::sniff()),
+            "matador" => Some(Rules::matador()),
+            "bergen" => Some(Rules::bergen()),
+            _ => None,
+        }
+    }
+
+    /// Builds rules from a variant name followed by `key=value` table options,
+    /// e.g. `brazilian target=10 galo=off`.
+    pub fn from_options(options: &str) -> Result<Rules> {
+        let mut words = options.split_whitespace();
+        let name = words.next().unwrap_or("block");
+        let mut rules = Rules::by_name(name).ok_or_else(|| anyhow!("Unknown variant {}", name))?;
+
+        for word in words {
+            let (key, value) = word
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Expected key=value, got {}", word))?;
+
+            match key {
+                "target" => rules.goal = Goal::Target(value.parse()?),
+                "hands" => rules.goal = Goal::Hands(value.parse()?),
+                "duplicate" => rules.goal = Goal::Duplicate(value.parse()?),
+                "deal-seed" => rules.deal_seed = Some(value.parse()?),
+                "partnerships" => rules.partnerships = parse_switch(value)?,
+                "galo" => rules.galo = parse_switch(value)?,
+                "draw" => rules.draw = parse_switch(value)?,
+                "tranque" => rules.tranque = parse_tranque(value)?,
+                "spinner" => rules.spinner = parse_switch(value)?,
+                "fives" => rules.fives = parse_switch(value)?,
+                "headers" => rules.headers = parse_switch(value)?,
+                "sum-to" => rules.matching = Matching::SumTo(value.parse()?),
+                "batida" => rules.bonuses.batida = value.parse()?,
+                "carroca" => rules.bonuses.carroca = value.parse()?,
+                "capicua" => rules.bonuses.capicua = value.parse()?,
+                "la-e-lo" => rules.bonuses.la_e_lo = value.parse()?,
+                "misdeal-doubles" => rules.misdeals.push(Misdeal::Doubles(value.parse()?)),
+                "misdeal-pips" => rules.misdeals.push(Misdeal::FewPips(value.parse()?)),
+                _ => return Err(anyhow!("Unknown option {}", key)),
+            }
+        }
+
+        Ok(rules)
+    }
+
+    pub fn set_size(&self) -> usize {
+        let n = (self.max_pip + 1) as usize;
+        n * (n + 1) / 2
+    }
+
+    pub fn hand_size(&self, n_players: i32) -> usize {
+        match (self.variant, n_players) {
+            (Variant::ChickenFoot, 4) => 11,
+            (Variant::ChickenFoot, _) => 15,
+            (Variant::Caribbean, _) => 10,
+            (Variant::Sniff | Variant::Matador, 2) => 7,
+            (Variant::Sniff | Variant::Matador, _) => 5,
+            (Variant::Bergen, _) => 6,
+            (_, 2) => 14,
+            (_, 3) => 9,
+            _ => 7,
+        }
+    }
+
+    /// The double that has to open the given hand, if the variant imposes one.
+    pub fn opening_double(&self, hand: usize) -> Option<Domino> {
+        match self.variant {
+            Variant::ChickenFoot => {
+                let pips = self.max_pip - (hand as i32 % (self.max_pip + 1));
+                Some(Domino(pips, pips))
+            }
+            Variant::Brazilian if hand == 0 => Some(Domino(self.max_pip, self.max_pip)),
+            _ => None,
+        }
+    }
+
+    /// Number of tiles that must be played on a freshly placed double before
+    /// play may continue elsewhere. The opening double is `first`.
+    pub fn foot_size(&self, first: bool) -> usize {
+        match (self.variant, first) {
+            (Variant::ChickenFoot, true) => 6,
+            (Variant::ChickenFoot, false) => 3,
+            _ => 0,
+        }
+    }
+
+    pub fn matches(&self, half: i32, end: i32) -> bool {
+        match self.matching {
+            Matching::Equal => half == end,
+            Matching::SumTo(sum) => half + end == sum,
+        }
+    }
+
+    pub fn is_wild(&self, domino: &Domino) -> bool {
+        match self.matching {
+            Matching::Equal => false,
+            Matching::SumTo(sum) => domino.pips() == sum || *domino == Domino(0, 0),
+        }
+    }
+
+    /// Orients `domino` to follow an end showing `number`, with `.0` touching
+    /// it, if it can be played there. Wild tiles that match neither way keep
+    /// their orientation.
+    pub fn place(&self, domino: &Domino, number: i32) -> Option<Domino> {
+        domino
+            .match_right_with(number, |half, end| self.matches(half, end))
+            .or_else(|| Some(*domino).filter(|domino| self.is_wild(domino)))
+    }
+
+    /// Whether two seats play on the same side.
+    pub fn same_side(&self, n_players: usize, a: usize, b: usize) -> bool {
+        a == b || (self.partnerships && n_players == 4 && a % 2 == b % 2)
+    }
+
+    /// Points a tile left in hand is worth when the hand is scored.
+    pub fn pip_value(&self, domino: &Domino) -> i32 {
+        match (self.variant, domino) {
+            (Variant::ChickenFoot, Domino(0, 0)) => 50,
+            _ => domino.pips(),
+        }
+    }
+}
+
+fn parse_switch(value: &str) -> Result<bool> {
+    match value {
+        "on" | "true" | "yes" => Ok(true),
+        "off" | "false" | "no" => Ok(false),
+        _ => Err(anyhow!("Expected on or off, got {}", value)),
+    }
+}
+
+pub(crate) fn parse_tranque(value: &str) -> Result<Tranque> {
+    match value {
+        "hand" => Ok(Tranque::LowestHand),
+        "team" => Ok(Tranque::LowestTeam),
+        "blocker" => Ok(Tranque::Blocker),
+        _ => Err(anyhow!("Expected hand, team or blocker, got {}", value)),
+    }
+}
+
+/// The option value `parse_tranque` reads back.
+pub(crate) fn tranque_name(tranque: Tranque) -> &'static str {
+    match tranque {
+        Tranque::LowestHand => "hand",
+        Tranque::LowestTeam => "team",
+        Tranque::Blocker => "blocker",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let rules = Rules::from_options(
+            "block target=50 partnerships=on galo=yes draw=true tranque=blocker spinner=on \
+             fives=on headers=on sum-to=12 deal-seed=7",
+        )
+        .unwrap();
+
+        assert_eq!(Variant::Block, rules.variant);
+        assert_eq!(Goal::Target(50), rules.goal);
+        assert!(rules.partnerships && rules.galo && rules.draw);
+        assert!(rules.spinner && rules.fives && rules.headers);
+        assert_eq!(Tranque::Blocker, rules.tranque);
+        assert_eq!(Matching::SumTo(12), rules.matching);
+        assert_eq!(Some(7), rules.deal_seed);
+
+        let rules = Rules::from_options("brazilian galo=off headers=no hands=3").unwrap();
+        assert!(!rules.galo && !rules.headers);
+        assert_eq!(Goal::Hands(3), rules.goal);
+
+        let rules = Rules::from_options(
+            "brazilian batida=2 carroca=3 capicua=4 la-e-lo=5 misdeal-doubles=5 misdeal-pips=10",
+        )
+        .unwrap();
+        assert_eq!(
+            Bonuses {
+                batida: 2,
+                carroca: 3,
+                capicua: 4,
+                la_e_lo: 5,
+            },
+            rules.bonuses
+        );
+        assert_eq!(
+            vec![Misdeal::Doubles(5), Misdeal::FewPips(10)],
+            rules.misdeals
+        );
+
+        let rules = Rules::from_options("block duplicate=4 tranque=hand").unwrap();
+        assert_eq!(Goal::Duplicate(4), rules.goal);
+        assert_eq!(Tranque::LowestHand, rules.tranque);
+        assert_eq!(Rules::block(), Rules::from_options("").unwrap());
+    }
+
+    #[test]
+    fn test_bad_options() {
+        for options in [
+            "dominoes",
+            "block colour=red",
+            "block target",
+            "block hands=x",
+            "block tranque=foo",
+            "block sum-to=",
+            "block galo=maybe",
+            "block deal-seed=-1",
+            "brazilian misdeal-doubles=many",
+        ] {
+            assert!(Rules::from_options(options).is_err(), "{}", options);
+        }
+    }
+}
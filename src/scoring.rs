@@ -0,0 +1,480 @@
+use crate::game::{Game, Outcome, Placement};
+use crate::log::{Event, GameLog};
+use crate::rules::{Goal, Misdeal, Rules, Variant};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+
+/// Deals thrown in before a usable one comes out, so that a house rule no
+/// deal can satisfy does not stall the table.
+const MAX_REDEALS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HandResult {
+    pub outcome: Outcome,
+    /// Points scored by each seat in this hand, credited to both partners in
+    /// partnership play.
+    pub points: [i32; 4],
+    /// Cumulative scores after this hand of the player at each seat.
+    pub totals: [i32; 4],
+    pub finished: bool,
+}
+
+/// A sequence of hands played under the same rules, keeping the score.
+/// Players sit at the seat of the same number, except in duplicate matches
+/// where they move round the table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match {
+    pub rules: Rules,
+    /// Scores of each player.
+    pub scores: [i32; 4],
+    pub hand: usize,
+    /// Points of tied blocked hands waiting for the next winner.
+    pub carry: i32,
+    pub log: GameLog,
+    /// Misdeals thrown in before the current hand.
+    pub misdeals: Vec<(usize, Misdeal)>,
+    /// The player whose side gave the match up, if one did.
+    pub forfeited: Option<usize>,
+    starter: Option<usize>,
+    /// Seed the boards are dealt from, when deals are seeded.
+    seed: Option<u64>,
+    n_players: i32,
+}
+
+impl Match {
+    pub fn new(rules: Rules, n_players: i32) -> Match {
+        Match {
+            rules,
+            scores: [0; 4],
+            hand: 0,
+            carry: 0,
+            log: GameLog::new(),
+            misdeals: Vec::new(),
+            forfeited: None,
+            starter: None,
+            seed: None,
+            n_players,
+        }
+    }
+
+    fn duplicate(&self) -> bool {
+        matches!(self.rules.goal, Goal::Duplicate(_))
+    }
+
+    /// Times each board is played: once per seat in duplicate matches.
+    fn rotations(&self) -> usize {
+        match self.duplicate() {
+            true => self.n_players as usize,
+            false => 1,
+        }
+    }
+
+    /// The deal being played, the same for every hand of a board.
+    pub fn board(&self) -> usize {
+        self.hand / self.rotations()
+    }
+
+    /// Seats the players have moved round for the current hand.
+    pub fn rotation(&self) -> usize {
+        self.hand % self.rotations()
+    }
+
+    /// Seat of the given player in the current hand.
+    pub fn seat(&self, player: usize) -> usize {
+        (player + self.rotation()) % self.n_players as usize
+    }
+
+    fn player_at(&self, seat: usize) -> usize {
+        let n_players = self.n_players as usize;
+        (seat + n_players - self.rotation()) % n_players
+    }
+
+    /// Deals the next hand, reshuffling as long as a hand breaks one of the
+    /// misdeal rules. Unless the rules name an opening double, the hand is
+    /// opened by the winner of the previous one.
+    pub fn deal(&mut self) -> Game {
+        self.deal_with(&mut thread_rng())
+    }
+
+    /// Deals the next hand with the given source of randomness. Seeded and
+    /// duplicate matches draw their seed from it once and deal every board
+    /// from that, so that the hands of a board are the same.
+    pub fn deal_with(&mut self, rng: &mut impl Rng) -> Game {
+        match self.board_seed(rng) {
+            Some(seed) => self.deal_from(&mut StdRng::seed_from_u64(seed)),
+            None => self.deal_from(rng),
+        }
+    }
+
+    fn board_seed(&mut self, rng: &mut impl Rng) -> Option<u64> {
+        if self.rules.deal_seed.is_none() && !self.duplicate() {
+            return None;
+        }
+
+        let seed = *self
+            .seed
+            .get_or_insert_with(|| self.rules.deal_seed.unwrap_or_else(|| rng.gen()));
+        Some(seed ^ (self.board() as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    fn deal_from(&mut self, rng: &mut impl Rng) -> Game {
+        self.misdeals.clear();
+        let board = self.board();
+
+        loop {
+            let mut game = Game::deal_with(self.rules.clone(), self.n_players, board, rng);
+
+            if let (Some(seat), None, false) = (
+                self.starter,
+                self.rules.opening_double(board),
+                self.duplicate(),
+            ) {
+                game.next = seat as i32;
+            }
+
+            self.log.deal(self.hand, &game);
+
+            match game.misdeal() {
+                Some((seat, misdeal)) if self.misdeals.len() < MAX_REDEALS => {
+                    self.log.push(Event::Misdeal { seat, misdeal });
+                    self.misdeals.push((seat, misdeal));
+                }
+                _ => return game,
+            }
+        }
+    }
+
+    pub fn record(&mut self, game: &Game, outcome: Outcome) -> HandResult {
+        let mut points = hand_points(&self.rules, game, outcome);
+
+        if self.rules.galo {
+            if points.iter().all(|points| *points == 0) {
+                self.carry += self.rules.bonuses.batida;
+            } else {
+                for points in points.iter_mut().filter(|points| **points > 0) {
+                    *points += self.carry;
+                }
+                self.carry = 0;
+            }
+        }
+
+        self.starter = match outcome {
+            Outcome::Won(seat) => Some(seat),
+            Outcome::Blocked => game.blocked_winner().or(self.starter),
+        };
+
+        let n_players = self.n_players as usize;
+        let mut totals = [0; 4];
+        for seat in 0..n_players {
+            let player = self.player_at(seat);
+            self.scores[player] += points[seat];
+            totals[seat] = self.scores[player];
+        }
+        self.hand += 1;
+
+        let result = HandResult {
+            outcome,
+            points,
+            totals,
+            finished: self.is_over(),
+        };
+        self.log.push(Event::HandOver(result));
+
+        result
+    }
+
+    pub fn is_over(&self) -> bool {
+        if self.forfeited.is_some() {
+            return true;
+        }
+
+        match self.rules.goal {
+            Goal::Hands(hands) => self.hand >= hands,
+            Goal::Duplicate(boards) => self.hand >= boards * self.n_players as usize,
+            Goal::Target(target) => self.scores.iter().any(|score| *score >= target),
+        }
+    }
+
+    pub fn winner(&self) -> Option<usize> {
+        if !self.is_over() {
+            return None;
+        }
+
+        let n_players = self.n_players as usize;
+        // A forfeit hands the match to the best of the other side.
+        let seats = (0..n_players).filter(|player| match self.forfeited {
+            Some(loser) => !self
+                .rules
+                .same_side(n_players, self.seat(*player), self.seat(loser)),
+            None => true,
+        });
+
+        if self.rules.lowest_wins {
+            seats.min_by_key(|seat| self.scores[*seat])
+        } else {
+            seats.max_by_key(|seat| self.scores[*seat])
+        }
+    }
+
+    /// Ends the match, lost by the side of the player at the given seat.
+    pub fn forfeit(&mut self, seat: usize) {
+        self.forfeited = Some(self.player_at(seat));
+    }
+
+    /// Points each player scored on each board so far, to compare players
+    /// who held the same hands.
+    pub fn boards(&self) -> Vec<[i32; 4]> {
+        let n_players = self.n_players as usize;
+        let rotations = self.rotations();
+        let mut boards = Vec::new();
+
+        let results = self.log.events.iter().filter_map(|event| match event {
+            Event::HandOver(result) => Some(result),
+            _ => None,
+        });
+
+        for (hand, result) in results.enumerate() {
+            if boards.len() <= hand / rotations {
+                boards.push([0; 4]);
+            }
+            let board = boards.last_mut().unwrap();
+            let rotation = hand % rotations;
+
+            for (seat, points) in result.points.iter().enumerate().take(n_players) {
+                board[(seat + n_players - rotation) % n_players] += points;
+            }
+        }
+
+        boards
+    }
+}
+
+/// Points for going out in Brazilian dominoes, judged by the last tile and
+/// the ends it was played against.
+fn batida_points(rules: &Rules, game: &Game) -> i32 {
+    let bonuses = &rules.bonuses;
+
+    let (domino, (left, right)) = match game.last {
+        Some(Placement {
+            domino,
+            line_ends: Some(ends),
+            ..
+        }) => (domino, ends),
+        _ => return bonuses.batida,
+    };
+
+    let fits_both =
+        (domino.0 == left && domino.1 == right) || (domino.0 == right && domino.1 == left);
+
+    match (domino.is_double(), fits_both) {
+        (true, true) => bonuses.la_e_lo,
+        (true, false) => bonuses.carroca,
+        (false, true) if left != right => bonuses.capicua,
+        _ => bonuses.batida,
+    }
+}
+
+/// Points each seat scores for a finished hand.
+///
+/// When the lowest score wins, every seat is charged the tiles left in its
+/// hand. Otherwise the winner, or whoever takes a blocked game under the
+/// tranque rule, takes the pips left in the opponents' hands, or the
+/// Brazilian bonus for the winning tile; a tied blocked game scores nothing.
+/// Points scored on the ends during the hand are added on top.
+pub fn hand_points(rules: &Rules, game: &Game, outcome: Outcome) -> [i32; 4] {
+    let n_players = game.n_players() as usize;
+    let mut pips = [0; 4];
+    for (seat, pips) in pips.iter_mut().enumerate().take(n_players) {
+        *pips = game.pips(seat);
+    }
+
+    if rules.lowest_wins {
+        return pips;
+    }
+
+    let winner = match outcome {
+        Outcome::Won(seat) => Some(seat),
+        Outcome::Blocked => game.blocked_winner(),
+    };
+
+    let mut points = [0; 4];
+
+    if let Some(seat) = winner {
+        let value = match (rules.variant, outcome) {
+            (Variant::Brazilian, Outcome::Won(_)) => batida_points(rules, game),
+            (Variant::Brazilian, Outcome::Blocked) => rules.bonuses.batida,
+            (Variant::Bergen, _) => 1,
+            _ => (0..n_players)
+                .filter(|other| !rules.same_side(n_players, seat, *other))
+                .map(|other| pips[other])
+                .sum(),
+        };
+
+        let value = if rules.fives {
+            (value + 2) / 5 * 5
+        } else {
+            value
+        };
+
+        for (other, points) in points.iter_mut().enumerate().take(n_players) {
+            if rules.same_side(n_players, seat, other) {
+                *points = value;
+            }
+        }
+    }
+
+    for (seat, points) in points.iter_mut().enumerate().take(n_players) {
+        *points += (0..n_players)
+            .filter(|other| rules.same_side(n_players, seat, *other))
+            .map(|other| game.points[other])
+            .sum::<i32>();
+    }
+
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::{Domino, End};
+
+    #[test]
+    fn test_chicken_foot_match() {
+        let rules = Rules::chicken_foot();
+        let mut match_ = Match::new(rules, 2);
+
+        let mut game = match_.deal();
+        game.players[0] = vec![Domino(0, 0), Domino(3, 2)];
+        game.players[1] = vec![];
+
+        let result = match_.record(&game, Outcome::Won(1));
+
+        assert_eq!([55, 0, 0, 0], result.points);
+        assert!(!result.finished);
+
+        for _ in 1..10 {
+            match_.record(&game, Outcome::Won(1));
+        }
+
+        assert!(match_.is_over());
+        assert_eq!(Some(1), match_.winner());
+    }
+
+    #[test]
+    fn test_block_points() {
+        let mut game = Game::new(2);
+        game.players[0] = vec![Domino(1, 1)];
+        game.players[1] = vec![Domino(6, 5), Domino(2, 0)];
+
+        let points = hand_points(&Rules::block(), &game, Outcome::Blocked);
+
+        assert_eq!([13, 0, 0, 0], points);
+    }
+
+    fn brazilian_game(last: Domino, line_ends: (i32, i32)) -> Game {
+        let mut game = Game::deal(Rules::brazilian(), 4, 1);
+        game.players[1] = vec![];
+        game.last = Some(Placement {
+            player: 1,
+            domino: last,
+            end: End::Right,
+            line_ends: Some(line_ends),
+        });
+        game
+    }
+
+    #[test]
+    fn test_brazilian_batida() {
+        let rules = Rules::brazilian();
+        let cases = [
+            (Domino(2, 5), (3, 2), 1),
+            (Domino(4, 4), (4, 1), 2),
+            (Domino(3, 1), (1, 3), 3),
+            (Domino(6, 6), (6, 6), 4),
+        ];
+
+        for (domino, ends, expected) in cases {
+            let game = brazilian_game(domino, ends);
+            let points = hand_points(&rules, &game, Outcome::Won(1));
+
+            assert_eq!([0, expected, 0, expected], points);
+        }
+    }
+
+    #[test]
+    fn test_galo_carries_over() {
+        let mut match_ = Match::new(Rules::brazilian(), 4);
+
+        let mut game = match_.deal();
+        game.players = [
+            vec![Domino(1, 2)],
+            vec![Domino(3, 0)],
+            vec![Domino(0, 0)],
+            vec![Domino(0, 0)],
+        ];
+
+        let result = match_.record(&game, Outcome::Blocked);
+        assert_eq!([0; 4], result.points);
+        assert_eq!(1, match_.carry);
+
+        let game = brazilian_game(Domino(4, 4), (4, 1));
+        let result = match_.record(&game, Outcome::Won(1));
+        assert_eq!([0, 3, 0, 3], result.points);
+        assert_eq!(0, match_.carry);
+        assert_eq!(1, match_.deal().next);
+    }
+
+    #[test]
+    fn test_table_options() {
+        let rules = Rules::from_options("brazilian target=10 galo=off").unwrap();
+
+        assert_eq!(Variant::Brazilian, rules.variant);
+        assert_eq!(Goal::Target(10), rules.goal);
+        assert!(!rules.galo);
+        assert!(Rules::from_options("brazilian galo=maybe").is_err());
+        assert!(Rules::from_options("canasta").is_err());
+    }
+
+    #[test]
+    fn test_duplicate_boards() {
+        let rules = Rules::from_options("block duplicate=2").unwrap();
+        let mut match_ = Match::new(rules, 4);
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let first = match_.deal_with(&mut rng);
+        let won = (1..4).map(|seat| first.pips(seat)).sum::<i32>();
+
+        for rotation in 0..4 {
+            let mut game = match_.deal_with(&mut rng);
+            assert_eq!(first.players, game.players);
+            assert_eq!(rotation, match_.rotation());
+            assert_eq!((2 + rotation) % 4, match_.seat(2));
+
+            game.players[0].clear();
+            let result = match_.record(&game, Outcome::Won(0));
+            assert_eq!(won, result.totals[0]);
+        }
+
+        assert_eq!(vec![[won; 4]], match_.boards());
+        assert_ne!(first.players, match_.deal_with(&mut rng).players);
+        assert_eq!(1, match_.board());
+        assert!(!match_.is_over());
+    }
+
+    #[test]
+    fn test_misdeal_redeals() {
+        let mut rules = Rules::block();
+        rules.misdeals.push(Misdeal::FewPips(30));
+        let mut match_ = Match::new(rules, 4);
+
+        let game = match_.deal();
+
+        assert_eq!(None, game.misdeal());
+        assert_eq!(match_.misdeals.len() + 1, match_.log.deals(0).len());
+
+        for (seat, misdeal) in &match_.misdeals {
+            assert_eq!(Misdeal::FewPips(30), *misdeal);
+            assert!(*seat < 4);
+        }
+    }
+}